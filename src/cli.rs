@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use crate::models::{DeviceType, GpuType, HintLevel, Mode};
+use crate::pacing::Speed;
+
+/// Arch Linux Installation Simulator
+#[derive(Debug, Parser)]
+#[command(name = "archtry", version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Print without colors (also honours NO_COLOR)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Without a subcommand, `archtry` behaves like `archtry run`
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start a lesson (the default)
+    Run(RunArgs),
+    /// Print the commands typed in earlier sessions
+    History,
+    /// Forget the command history and the saved session
+    Reset,
+    /// List the bundled and installed lessons
+    Scenarios,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct RunArgs {
+    /// Lesson to run: a TOML file, or the name of an installed lesson
+    pub scenario: Option<String>,
+
+    /// GPU to install drivers for, instead of asking
+    #[arg(long, value_enum)]
+    pub gpu: Option<GpuType>,
+
+    /// Kind of machine, instead of asking
+    #[arg(long, value_enum)]
+    pub device: Option<DeviceType>,
+
+    /// Desktop environment to install, instead of asking (e.g. gnome, plasma, none)
+    #[arg(long = "de", value_name = "DESKTOP")]
+    pub desktop: Option<String>,
+
+    /// How long pauses and progress bars take [default: normal, or instant without a terminal]
+    #[arg(long, value_enum)]
+    pub speed: Option<Speed>,
+
+    /// How much help the lesson gives [default: guided]
+    #[arg(long, value_enum)]
+    pub mode: Option<Mode>,
+
+    /// How much each command step gives away in guided mode [default: full]
+    #[arg(long, value_enum)]
+    pub hints: Option<HintLevel>,
+
+    /// Wrong attempts at a step before offering the answer, an explanation
+    /// or a skip; 0 never offers [default: 3]
+    #[arg(long, value_name = "N")]
+    pub max_attempts: Option<u32>,
+
+    /// Start at a chapter such as `bootloader`, doing the earlier ones with
+    /// default answers; without an id, choose from a menu
+    #[arg(long, value_name = "ID", num_args = 0..=1)]
+    pub chapter: Option<Option<String>>,
+
+    /// Answer every prompt from FILE, one line each, for unattended runs
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Keep this run's commands out of the history file
+    #[arg(long)]
+    pub no_history: bool,
+}
@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use crate::shell;
+use crate::vfs::normalize;
+
+/// Output redirection: `> path` or `>> path`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub append: bool,
+    pub path: String,
+}
+
+/// A simple command after expansion: program, arguments and an optional redirection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandLine {
+    /// Leading `NAME=value` words
+    pub assignments: Vec<(String, String)>,
+    pub words: Vec<String>,
+    pub redirect: Option<Redirect>,
+    /// The command as typed, before expansion
+    pub text: String,
+}
+
+impl CommandLine {
+    /// Returns the program name, if any
+    pub fn program(&self) -> Option<&str> {
+        self.words.first().map(String::as_str)
+    }
+
+    /// Returns the arguments after the program name
+    pub fn args(&self) -> Vec<&str> {
+        self.words.iter().skip(1).map(String::as_str).collect()
+    }
+}
+
+/// Per-step overrides for what counts as the right command
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchRules {
+    /// Require the exact text, only ignoring surrounding and repeated spaces
+    #[serde(default)]
+    pub exact: bool,
+    /// Other command lines accepted for the step
+    #[serde(default)]
+    pub alternatives: Vec<String>,
+    /// Allow operands after the program's fixed ones in any order
+    pub unordered: Option<bool>,
+    /// Allow operands beyond the expected ones, e.g. extra packages
+    pub extra_operands: Option<bool>,
+}
+
+/// Option a program understands, by short and long spelling
+struct FlagSpec {
+    short: Option<char>,
+    long: Option<&'static str>,
+    takes_value: bool,
+}
+
+const fn flag(short: char, long: &'static str) -> FlagSpec {
+    FlagSpec { short: Some(short), long: Some(long), takes_value: false }
+}
+
+const fn short(short: char) -> FlagSpec {
+    FlagSpec { short: Some(short), long: None, takes_value: false }
+}
+
+const fn long(long: &'static str) -> FlagSpec {
+    FlagSpec { short: None, long: Some(long), takes_value: false }
+}
+
+const fn valued(short: Option<char>, long: &'static str) -> FlagSpec {
+    FlagSpec { short, long: Some(long), takes_value: true }
+}
+
+/// How a program's arguments compare
+struct ProgramSpec {
+    names: &'static [&'static str],
+    flags: &'static [FlagSpec],
+    /// Leading operands whose position matters
+    fixed_operands: usize,
+    unordered: bool,
+    extra_operands: bool,
+    /// Flags that never change the outcome of the lesson
+    harmless: &'static [&'static str],
+}
+
+const PROGRAMS: &[ProgramSpec] = &[
+    ProgramSpec {
+        names: &["mount"],
+        flags: &[flag('m', "mkdir"), valued(Some('o'), "options"), valued(Some('t'), "types")],
+        fixed_operands: 2, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["mkdir"],
+        flags: &[flag('p', "parents"), flag('v', "verbose"), valued(Some('m'), "mode")],
+        fixed_operands: 0, unordered: true, extra_operands: false, harmless: &["verbose"],
+    },
+    ProgramSpec {
+        names: &["umount"],
+        flags: &[flag('R', "recursive"), flag('l', "lazy")],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["pacstrap"],
+        flags: &[short('K'), short('c'), short('G'), short('i'), short('P')],
+        fixed_operands: 1, unordered: true, extra_operands: true, harmless: &["-i", "-P"],
+    },
+    ProgramSpec {
+        names: &["pacman"],
+        flags: &[flag('S', "sync"), flag('y', "refresh"), flag('u', "sysupgrade"), long("needed"), long("noconfirm")],
+        fixed_operands: 0, unordered: true, extra_operands: true, harmless: &["needed", "noconfirm"],
+    },
+    ProgramSpec {
+        names: &["useradd"],
+        flags: &[flag('m', "create-home"), valued(Some('G'), "groups"), valued(Some('s'), "shell"), valued(Some('g'), "gid")],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["ln"],
+        flags: &[flag('s', "symbolic"), flag('f', "force"), flag('v', "verbose")],
+        fixed_operands: 2, unordered: false, extra_operands: false, harmless: &["verbose"],
+    },
+    ProgramSpec {
+        names: &["mkfs.fat", "mkfs.vfat"],
+        flags: &[valued(Some('F'), "fat-size"), valued(Some('n'), "volume-name")],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &["volume-name"],
+    },
+    ProgramSpec {
+        names: &["mkfs.ext4"],
+        flags: &[valued(Some('L'), "label"), short('F'), short('q')],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &["label", "-F", "-q"],
+    },
+    ProgramSpec {
+        names: &["grub-install"],
+        flags: &[valued(None, "target"), valued(None, "efi-directory"), valued(None, "bootloader-id"), long("removable")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["grub-mkconfig"],
+        flags: &[valued(Some('o'), "output")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["genfstab"],
+        flags: &[short('U'), short('L'), short('p'), short('P')],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["hwclock"],
+        flags: &[flag('w', "systohc"), flag('v', "verbose")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &["verbose"],
+    },
+    ProgramSpec {
+        names: &["sed"],
+        flags: &[flag('i', "in-place"), valued(Some('e'), "expression")],
+        fixed_operands: 2, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["fdisk"],
+        flags: &[flag('l', "list")],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["grep"],
+        flags: &[flag('i', "ignore-case"), flag('v', "invert-match")],
+        fixed_operands: 1, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["head"],
+        flags: &[valued(Some('n'), "lines")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["tail"],
+        flags: &[valued(Some('n'), "lines")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["wc"],
+        flags: &[flag('l', "lines")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["tee"],
+        flags: &[flag('a', "append")],
+        fixed_operands: 0, unordered: false, extra_operands: false, harmless: &[],
+    },
+    ProgramSpec {
+        names: &["lsblk"],
+        flags: &[flag('f', "fs"), flag('p', "paths")],
+        fixed_operands: 0, unordered: true, extra_operands: false, harmless: &["paths"],
+    },
+];
+
+/// Arguments split getopt-style into flags and operands.
+/// Flags are keyed by their long name, or `-x` when a program has none.
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    flags: BTreeMap<String, Option<String>>,
+    pub operands: Vec<String>,
+}
+
+impl Options {
+    /// Parses `args` using the flag table of `program`
+    pub fn parse(program: &str, args: &[&str]) -> Options {
+        let flags_spec = spec(program).map_or(&[][..], |s| s.flags);
+        let key = |f: &FlagSpec| f.long.map(str::to_string).unwrap_or_else(|| format!("-{}", f.short.unwrap_or('?')));
+        let mut options = Options::default();
+        let mut iter = args.iter().copied();
+        let mut only_operands = false;
+
+        while let Some(arg) = iter.next() {
+            if only_operands || arg == "-" || !arg.starts_with('-') {
+                options.operands.push(arg.to_string());
+            } else if arg == "--" {
+                only_operands = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let known = flags_spec.iter().find(|f| f.long == Some(name));
+                let value = match known {
+                    Some(f) if f.takes_value && inline.is_none() => iter.next().map(str::to_string),
+                    _ => inline,
+                };
+                options.flags.insert(known.map_or(format!("--{}", name), key), value);
+            } else {
+                let letters: Vec<char> = arg[1..].chars().collect();
+                for (index, letter) in letters.iter().enumerate() {
+                    let known = flags_spec.iter().find(|f| f.short == Some(*letter));
+                    let name = known.map_or(format!("-{}", letter), key);
+                    if known.is_some_and(|f| f.takes_value) {
+                        let rest: String = letters[index + 1..].iter().collect();
+                        let value = if rest.is_empty() { iter.next().map(str::to_string) } else { Some(rest) };
+                        options.flags.insert(name, value);
+                        break;
+                    }
+                    options.flags.insert(name, None);
+                }
+            }
+        }
+        options
+    }
+
+    /// Returns true if the flag was given, by long name or `-x`
+    pub fn has(&self, name: &str) -> bool {
+        self.flags.contains_key(name)
+    }
+
+    /// Returns the value given to a flag
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).and_then(|v| v.as_deref())
+    }
+
+    /// Returns the operands as string slices
+    pub fn operands(&self) -> Vec<&str> {
+        self.operands.iter().map(String::as_str).collect()
+    }
+}
+
+/// A command reduced to comparable parts
+#[derive(Debug, PartialEq)]
+struct Invocation {
+    program: String,
+    options: Options,
+    redirect: Option<Redirect>,
+}
+
+/// Returns the spec for a program name
+fn spec(program: &str) -> Option<&'static ProgramSpec> {
+    PROGRAMS.iter().find(|spec| spec.names.contains(&program))
+}
+
+/// Returns every spelling of the flags a program understands
+pub fn flags(program: &str) -> Vec<String> {
+    let flags = spec(program).map_or(&[][..], |s| s.flags);
+    flags
+        .iter()
+        .flat_map(|f| [f.short.map(|c| format!("-{}", c)), f.long.map(|l| format!("--{}", l))])
+        .flatten()
+        .collect()
+}
+
+/// Returns the first flag in `args` that `program` does not understand, as
+/// typed: `-x` or `--name`. Programs without a flag table accept anything.
+pub fn unknown_flag(program: &str, args: &[&str]) -> Option<String> {
+    let flags = spec(program)?.flags;
+    let mut iter = args.iter().copied();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            return None;
+        }
+        if arg == "-" || !arg.starts_with('-') {
+            continue;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = long.split_once('=').map_or((long, false), |(name, _)| (name, true));
+            match flags.iter().find(|f| f.long == Some(name)) {
+                None => return Some(format!("--{}", name)),
+                Some(f) if f.takes_value && !inline => {
+                    iter.next();
+                }
+                Some(_) => {}
+            }
+            continue;
+        }
+        let letters = &arg[1..];
+        for (index, letter) in letters.char_indices() {
+            match flags.iter().find(|f| f.short == Some(letter)) {
+                None => return Some(format!("-{}", letter)),
+                Some(f) if f.takes_value => {
+                    // The value is the rest of the word, or else the next one
+                    if index + letter.len_utf8() == letters.len() {
+                        iter.next();
+                    }
+                    break;
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    None
+}
+
+/// Normalizes paths so `/mnt/` and `/mnt` compare equal
+fn canonical_value(value: &str) -> String {
+    if value.starts_with('/') { normalize(value) } else { value.to_string() }
+}
+
+/// Reduces a command line to its program, options and redirection
+fn invocation(line: &CommandLine) -> Option<Invocation> {
+    let program = line.program()?;
+    let mut options = Options::parse(program, &line.args());
+    for value in options.flags.values_mut().flatten() {
+        *value = canonical_value(value);
+    }
+    options.operands = match program {
+        // echo only cares about what it prints
+        "echo" => vec![options.operands.join(" ")],
+        _ => options.operands.iter().map(|o| canonical_value(o)).collect(),
+    };
+    let program = spec(program).map_or(program, |s| s.names[0]).to_string();
+    let redirect = line.redirect.clone().map(|r| Redirect { append: r.append, path: normalize(&r.path) });
+    Some(Invocation { program, options, redirect })
+}
+
+/// Returns true if `input` does what `expected` does under the step's rules
+pub fn matches(input: &CommandLine, expected: &str, rules: &MatchRules) -> bool {
+    std::iter::once(expected)
+        .chain(rules.alternatives.iter().map(String::as_str))
+        .any(|candidate| matches_one(input, candidate, rules))
+}
+
+fn matches_one(input: &CommandLine, expected: &str, rules: &MatchRules) -> bool {
+    let Ok(script) = shell::parse(expected) else {
+        return false;
+    };
+    let [expected] = &script.commands(&|_| None)[..] else {
+        return false;
+    };
+    if rules.exact {
+        let squash = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+        return squash(&input.text) == squash(&expected.text);
+    }
+    let (Some(mut got), Some(mut want)) = (invocation(input), invocation(expected)) else {
+        return false;
+    };
+    if got.program != want.program || got.redirect != want.redirect {
+        return false;
+    }
+
+    let spec = spec(&got.program);
+    if let Some(spec) = spec {
+        for name in spec.harmless {
+            got.options.flags.remove(*name);
+            want.options.flags.remove(*name);
+        }
+    }
+    if got.options.flags != want.options.flags {
+        return false;
+    }
+
+    let (got, want) = (got.options.operands, want.options.operands);
+    let fixed = spec.map_or(0, |s| s.fixed_operands).min(want.len());
+    let unordered = rules.unordered.unwrap_or(spec.is_some_and(|s| s.unordered));
+    let extra = rules.extra_operands.unwrap_or(spec.is_some_and(|s| s.extra_operands));
+    if got.len() < fixed || got[..fixed] != want[..fixed] {
+        return false;
+    }
+
+    let mut got_rest = got[fixed..].to_vec();
+    let mut want_rest = want[fixed..].to_vec();
+    if unordered {
+        got_rest.sort();
+        want_rest.sort();
+    }
+    match (unordered, extra) {
+        (_, false) => got_rest == want_rest,
+        (true, true) => want_rest.iter().all(|w| got_rest.contains(w)),
+        (false, true) => got_rest.starts_with(&want_rest),
+    }
+}
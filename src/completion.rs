@@ -0,0 +1,82 @@
+use crate::command;
+use crate::input_handler::Completer;
+use crate::system::{System, COMMANDS, PACKAGES};
+use crate::utils::after_last;
+
+/// Characters that end a word for completion purposes
+const SEPARATORS: &str = "|&;<>";
+
+/// Completes against the simulated machine, also offering the next word of
+/// the command the current step expects when the hint already shows it
+pub struct StepCompleter<'a> {
+    pub system: &'a System,
+    pub expected: Option<&'a str>,
+}
+
+impl Completer for StepCompleter<'_> {
+    fn complete(&self, line: &str, cursor: usize) -> (usize, Vec<String>) {
+        let before = &line[..cursor];
+        let start = after_last(before, |c| c.is_whitespace() || SEPARATORS.contains(c));
+        let word = &before[start..];
+        let segment = after_last(&before[..start], |c| "|&;".contains(c));
+        let previous: Vec<&str> = before[segment..start]
+            .split(|c: char| c.is_whitespace() || SEPARATORS.contains(c))
+            .filter(|w| !w.is_empty())
+            .collect();
+        let after_redirect = before[..start].trim_end().ends_with('>');
+
+        let mut candidates = match previous.first() {
+            None if !after_redirect => self.commands(),
+            Some(program) if word.starts_with('-') => command::flags(program),
+            _ if word.starts_with("/dev/") => self.devices(),
+            _ if word.starts_with('/') || after_redirect => self.paths(word),
+            Some(&("pacstrap" | "pacman")) => PACKAGES.iter().map(|p| p.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        candidates.extend(self.expected_word(&previous));
+        candidates.retain(|c| c.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+        (start, candidates)
+    }
+}
+
+impl StepCompleter<'_> {
+    fn commands(&self) -> Vec<String> {
+        COMMANDS.iter().map(|c| c.to_string()).collect()
+    }
+
+    /// Disks and partitions, which live outside the VFS
+    fn devices(&self) -> Vec<String> {
+        self.system
+            .disks
+            .iter()
+            .flat_map(|disk| {
+                std::iter::once(disk.path()).chain(disk.partitions.iter().map(|p| disk.partition_path(p.number)))
+            })
+            .collect()
+    }
+
+    /// Entries of the directory `word` points into, with `/` after directories
+    fn paths(&self, word: &str) -> Vec<String> {
+        let dir = &word[..word.rfind('/').map_or(0, |index| index + 1)];
+        let listed = if dir.is_empty() { "/" } else { dir };
+        let Ok(entries) = self.system.vfs.list(listed) else {
+            return Vec::new();
+        };
+        entries
+            .into_iter()
+            .map(|(name, _)| {
+                let path = format!("{}{}", dir, name);
+                if self.system.vfs.is_dir(&path) { format!("{}/", path) } else { path }
+            })
+            .collect()
+    }
+
+    /// The step's next word, if what is typed so far follows the expected command
+    fn expected_word(&self, previous: &[&str]) -> Option<String> {
+        let expected: Vec<&str> = self.expected?.split_whitespace().collect();
+        let follows = expected.len() > previous.len() && expected[..previous.len()] == *previous;
+        follows.then(|| expected[previous.len()].to_string())
+    }
+}
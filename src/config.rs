@@ -1,26 +1,43 @@
-use colored::Colorize;
-use dialoguer::Select;
+use std::collections::HashMap;
+use clap::ValueEnum;
+use crate::theme::{Role, Themed};
+use crate::frontend::Frontend;
 use crate::models::{DeviceType, GpuType, UserChoices};
-use crate::utils::sleep;
 
-/// Collects user choices for installation
-pub fn get_user_choices() -> UserChoices {
-    println!("{}", "Welcome to ArchTry - Arch Linux Installation Simulator!\n".bright_green());
-    sleep(2);
-    let gpu_type = get_gpu_type();
-    let device_type = get_device_type();
-    UserChoices::new(gpu_type, device_type)
+/// What is already known before the lesson asks anything
+#[derive(Debug, Default)]
+pub struct Presets {
+    /// Chosen on the command line, so never asked
+    pub gpu: Option<GpuType>,
+    pub device: Option<DeviceType>,
+    /// Answers preselected in dialogs, from the config file
+    pub defaults: HashMap<String, String>,
+}
+
+impl Presets {
+    /// Position of the configured default for `var` among `variants`, if valid
+    fn default_index<T: ValueEnum>(&self, var: &str, variants: &[T]) -> usize {
+        self.defaults
+            .get(var)
+            .and_then(|value| variants.iter().position(|v| v.to_possible_value().is_some_and(|p| p.matches(value, true))))
+            .unwrap_or(0)
+    }
+}
+
+/// Collects user choices for installation, asking only for those not preset
+pub fn get_user_choices(frontend: &mut dyn Frontend, presets: &Presets) -> UserChoices {
+    frontend.println(&"Welcome to ArchTry - Arch Linux Installation Simulator!\n".themed(Role::Success).to_string());
+    frontend.pause(2);
+    let gpu_type = presets.gpu.unwrap_or_else(|| get_gpu_type(frontend, presets.default_index("gpu", GpuType::value_variants())));
+    let device_type = presets.device.unwrap_or_else(|| get_device_type(frontend, presets.default_index("device", DeviceType::value_variants())));
+    let mut user_choices = UserChoices::new(gpu_type, device_type);
+    user_choices.defaults = presets.defaults.clone();
+    user_choices
 }
 
 /// Selects GPU type with default option
-fn get_gpu_type() -> GpuType {
-    let options = &["AMD", "Intel", "NVIDIA"];
-    let selection = Select::new()
-        .with_prompt("Choose your GPU type")
-        .items(&options[..])
-        .default(0)
-        .interact()
-        .unwrap_or(0);
+fn get_gpu_type(frontend: &mut dyn Frontend, default: usize) -> GpuType {
+    let selection = frontend.select("Choose your GPU type", &["AMD", "Intel", "NVIDIA"], default);
 
     match selection {
         0 => GpuType::Amd,
@@ -31,14 +48,8 @@ fn get_gpu_type() -> GpuType {
 }
 
 /// Selects device type with default option
-fn get_device_type() -> DeviceType {
-    let options = &["Laptop", "PC"];
-    let selection = Select::new()
-        .with_prompt("Do you want a laptop or PC?")
-        .items(&options[..])
-        .default(0)
-        .interact()
-        .unwrap_or(0);
+fn get_device_type(frontend: &mut dyn Frontend, default: usize) -> DeviceType {
+    let selection = frontend.select("Do you want a laptop or PC?", &["Laptop", "PC"], default);
 
     match selection {
         0 => DeviceType::Laptop,
@@ -0,0 +1,32 @@
+use crate::command;
+use crate::frontend::Memory;
+use crate::shell::Script;
+use crate::system::{System, COMMANDS};
+
+/// Bash builtins the simulated shell does not run; bash would, so they are
+/// never "not found"
+const BUILTINS: &[&str] = &["history", "help", "type", "alias", "source", ".", "set", "read", "test", "["];
+
+/// The error the shell or the program itself would print for a line the step
+/// did not accept, classified in the order bash meets them: an unknown
+/// program, an unknown flag, then whatever the program says about its
+/// operands and the machine. `None` if the line would succeed.
+pub fn error(system: &System, script: &Script) -> Option<String> {
+    for line in script.commands(&|name| system.var(name)) {
+        let Some(program) = line.program() else {
+            continue;
+        };
+        if !COMMANDS.contains(&program) && !BUILTINS.contains(&program) {
+            return Some(format!("bash: {}: command not found", program));
+        }
+        if let Some(flag) = command::unknown_flag(program, &line.args()) {
+            return Some(match flag.strip_prefix("--") {
+                Some(_) => format!("{}: unrecognized option '{}'", program, flag),
+                None => format!("{}: invalid option -- '{}'", program, &flag[1..]),
+            });
+        }
+    }
+    // Try the line on a copy of the machine: the step never lets it change the real one
+    let run = system.clone().run(script, &mut Memory::default());
+    run.output.into_iter().filter_map(Result::err).find(|stderr| !stderr.is_empty())
+}
@@ -0,0 +1,441 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::vfs::{fake_uuid, Vfs};
+
+/// Logical sector size of every simulated disk
+pub const SECTOR_SIZE: u64 = 512;
+
+/// Partitions start on 1 MiB boundaries, like fdisk's default alignment
+pub const ALIGNMENT: u64 = 2048;
+
+/// Sectors GPT reserves at the end of the disk for the backup header
+const GPT_BACKUP_SECTORS: u64 = 33;
+
+/// Known partition type with its GPT GUID, MBR id and fdisk alias
+#[derive(Debug, PartialEq)]
+pub struct PartitionType {
+    pub name: &'static str,
+    pub alias: &'static str,
+    pub guid: &'static str,
+    pub mbr_id: u8,
+    /// Name fdisk shows for the MBR id
+    pub mbr_name: &'static str,
+    /// Position in fdisk's GPT type list
+    pub gpt_number: u32,
+}
+
+impl PartitionType {
+    /// Returns the name fdisk shows under the given label
+    pub fn label_name(&self, label: Option<PartitionTable>) -> &'static str {
+        match label {
+            Some(PartitionTable::Dos) => self.mbr_name,
+            _ => self.name,
+        }
+    }
+}
+
+/// Partition types offered by fdisk, in its GPT listing order
+pub const PARTITION_TYPES: &[PartitionType] = &[
+    PartitionType { name: "EFI System", alias: "uefi", guid: "C12A7328-F81F-11D2-BA4B-00A0C93EC93B", mbr_id: 0xef, mbr_name: "EFI (FAT-12/16/32)", gpt_number: 1 },
+    PartitionType { name: "BIOS boot", alias: "bios", guid: "21686148-6449-6E6F-744E-656564454649", mbr_id: 0, mbr_name: "", gpt_number: 4 },
+    PartitionType { name: "Linux swap", alias: "swap", guid: "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", mbr_id: 0x82, mbr_name: "Linux swap / Solaris", gpt_number: 19 },
+    PartitionType { name: "Linux filesystem", alias: "linux", guid: "0FC63DAF-8483-4772-8E79-3D69D8477DE4", mbr_id: 0x83, mbr_name: "Linux", gpt_number: 20 },
+    PartitionType { name: "Linux root (x86-64)", alias: "root", guid: "4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709", mbr_id: 0, mbr_name: "", gpt_number: 23 },
+    PartitionType { name: "Linux home", alias: "home", guid: "933AC7E1-2EB4-4F13-B844-0E14E2AEF915", mbr_id: 0, mbr_name: "", gpt_number: 42 },
+    PartitionType { name: "Linux LVM", alias: "lvm", guid: "E6D6D379-F507-44C2-A23C-238F2A3DF928", mbr_id: 0x8e, mbr_name: "Linux LVM", gpt_number: 44 },
+];
+
+/// Looks up a partition type by name
+pub fn partition_type(name: &str) -> &'static PartitionType {
+    PARTITION_TYPES.iter().find(|t| t.name == name).expect("known partition type")
+}
+
+/// On-disk partition table format
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PartitionTable {
+    Gpt,
+    Dos,
+}
+
+impl fmt::Display for PartitionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionTable::Gpt => write!(f, "gpt"),
+            PartitionTable::Dos => write!(f, "dos"),
+        }
+    }
+}
+
+/// Errors reported while editing a partition table
+#[derive(Debug, PartialEq)]
+pub enum DiskError {
+    NoLabel,
+    NoFreeSectors,
+    OutOfRange,
+    NoSuchPartition(u32),
+    PartitionExists(u32),
+}
+
+impl fmt::Display for DiskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiskError::NoLabel => write!(f, "Device does not contain a recognized partition table."),
+            DiskError::NoFreeSectors => write!(f, "No free sectors available."),
+            DiskError::OutOfRange => write!(f, "Value out of range."),
+            DiskError::NoSuchPartition(n) => write!(f, "Partition {} does not exist yet!", n),
+            DiskError::PartitionExists(n) => write!(f, "Partition {} is already defined.", n),
+        }
+    }
+}
+
+/// Contiguous range of sectors, both ends inclusive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Partition {
+    pub number: u32,
+    pub start: u64,
+    pub end: u64,
+    #[serde(with = "type_name")]
+    pub kind: &'static PartitionType,
+}
+
+/// Saves a partition type as its fdisk name, since the table is static
+mod type_name {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use super::{PartitionType, PARTITION_TYPES};
+
+    pub fn serialize<S: Serializer>(kind: &&'static PartitionType, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(kind.name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'static PartitionType, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        PARTITION_TYPES
+            .iter()
+            .find(|kind| kind.name == name)
+            .ok_or_else(|| de::Error::custom(format!("unknown partition type '{}'", name)))
+    }
+}
+
+impl Partition {
+    /// Returns the length in sectors
+    pub fn sectors(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Simulated block device with an optional partition table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Disk {
+    pub name: String,
+    pub model: String,
+    pub sectors: u64,
+    pub label: Option<PartitionTable>,
+    pub partitions: Vec<Partition>,
+}
+
+impl Disk {
+    /// Creates a blank disk of the given size in bytes
+    pub fn new(name: &str, model: &str, bytes: u64) -> Self {
+        Disk {
+            name: name.to_string(),
+            model: model.to_string(),
+            sectors: bytes / SECTOR_SIZE,
+            label: None,
+            partitions: Vec::new(),
+        }
+    }
+
+    /// Returns the device path, e.g. `/dev/sdz`
+    pub fn path(&self) -> String {
+        format!("/dev/{}", self.name)
+    }
+
+    /// Returns the device path of a partition, e.g. `/dev/sdz1`
+    pub fn partition_path(&self, number: u32) -> String {
+        let separator = if self.name.ends_with(|c: char| c.is_ascii_digit()) { "p" } else { "" };
+        format!("/dev/{}{}{}", self.name, separator, number)
+    }
+
+    /// Returns the partition number if `path` names one of this disk's partitions
+    pub fn partition_number(&self, path: &str) -> Option<u32> {
+        self.partitions.iter().map(|p| p.number).find(|&n| self.partition_path(n) == path)
+    }
+
+    /// Returns the disk size in bytes
+    pub fn bytes(&self) -> u64 {
+        self.sectors * SECTOR_SIZE
+    }
+
+    /// Returns a stable identifier for the partition table
+    pub fn identifier(&self) -> String {
+        match self.label {
+            Some(PartitionTable::Gpt) => fake_uuid(&self.path(), "gpt").to_uppercase(),
+            _ => format!("0x{}", &fake_uuid(&self.path(), "dos")[..8]),
+        }
+    }
+
+    /// Returns the partition's PARTUUID
+    pub fn partuuid(&self, number: u32) -> String {
+        match self.label {
+            Some(PartitionTable::Gpt) => fake_uuid(&self.partition_path(number), "partuuid"),
+            _ => format!("{}-{:02x}", &self.identifier()[2..], number),
+        }
+    }
+
+    /// Replaces the partition table with an empty one
+    pub fn create_label(&mut self, table: PartitionTable) {
+        self.label = Some(table);
+        self.partitions.clear();
+    }
+
+    /// Returns the highest partition number the table allows
+    pub fn max_partitions(&self) -> u32 {
+        match self.label {
+            Some(PartitionTable::Gpt) => 128,
+            _ => 4,
+        }
+    }
+
+    /// Returns the first sector a partition may use
+    pub fn first_usable(&self) -> u64 {
+        ALIGNMENT
+    }
+
+    /// Returns the last sector a partition may use
+    pub fn last_usable(&self) -> u64 {
+        match self.label {
+            Some(PartitionTable::Gpt) => self.sectors - GPT_BACKUP_SECTORS - 1,
+            _ => self.sectors - 1,
+        }
+    }
+
+    /// Returns the lowest unused partition number
+    pub fn next_number(&self) -> Option<u32> {
+        (1..=self.max_partitions()).find(|n| self.partition(*n).is_none())
+    }
+
+    /// Returns unallocated sector ranges, both ends inclusive
+    pub fn free_ranges(&self) -> Vec<(u64, u64)> {
+        let mut used: Vec<&Partition> = self.partitions.iter().collect();
+        used.sort_by_key(|p| p.start);
+        let mut ranges = Vec::new();
+        let mut cursor = self.first_usable();
+        for partition in used {
+            if partition.start > cursor {
+                ranges.push((cursor, partition.start - 1));
+            }
+            cursor = cursor.max(partition.end + 1);
+        }
+        if cursor <= self.last_usable() {
+            ranges.push((cursor, self.last_usable()));
+        }
+        ranges
+            .into_iter()
+            .map(|(start, end)| (start.div_ceil(ALIGNMENT) * ALIGNMENT, end))
+            .filter(|(start, end)| start <= end)
+            .collect()
+    }
+
+    /// Returns the free range containing `sector`
+    pub fn free_range_at(&self, sector: u64) -> Option<(u64, u64)> {
+        self.free_ranges().into_iter().find(|(start, end)| (*start..=*end).contains(&sector))
+    }
+
+    /// Returns a partition by number
+    pub fn partition(&self, number: u32) -> Option<&Partition> {
+        self.partitions.iter().find(|p| p.number == number)
+    }
+
+    /// Adds a `Linux filesystem` partition covering `start..=end`
+    pub fn add_partition(&mut self, number: u32, start: u64, end: u64) -> Result<(), DiskError> {
+        if self.label.is_none() {
+            return Err(DiskError::NoLabel);
+        }
+        if number == 0 || number > self.max_partitions() {
+            return Err(DiskError::OutOfRange);
+        }
+        if self.partition(number).is_some() {
+            return Err(DiskError::PartitionExists(number));
+        }
+        let Some((_, free_end)) = self.free_range_at(start) else {
+            return Err(DiskError::NoFreeSectors);
+        };
+        if end < start || end > free_end {
+            return Err(DiskError::OutOfRange);
+        }
+        self.partitions.push(Partition { number, start, end, kind: partition_type("Linux filesystem") });
+        self.partitions.sort_by_key(|p| p.number);
+        Ok(())
+    }
+
+    /// Removes a partition
+    pub fn delete_partition(&mut self, number: u32) -> Result<(), DiskError> {
+        if self.partition(number).is_none() {
+            return Err(DiskError::NoSuchPartition(number));
+        }
+        self.partitions.retain(|p| p.number != number);
+        Ok(())
+    }
+
+    /// Changes a partition's type
+    pub fn set_type(&mut self, number: u32, kind: &'static PartitionType) -> Result<(), DiskError> {
+        let partition = self
+            .partitions
+            .iter_mut()
+            .find(|p| p.number == number)
+            .ok_or(DiskError::NoSuchPartition(number))?;
+        partition.kind = kind;
+        Ok(())
+    }
+}
+
+/// Formats a byte count the way lsblk and fdisk do: `50G`, `1.5G`, `512M`
+pub fn human_size(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G", "T", "P"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{}{}", rounded, units[unit])
+    } else {
+        format!("{:.1}{}", rounded, units[unit])
+    }
+}
+
+/// Parses a size such as `512M`, `1G` or `300MiB` into bytes
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number: u64 = text[..digits].parse().ok()?;
+    let shift = match text[digits..].trim_end_matches("iB").trim_end_matches('B') {
+        "" => return Some(number),
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        "P" | "p" => 50,
+        _ => return None,
+    };
+    number.checked_mul(1 << shift)
+}
+
+/// Formats a size for fdisk's disk header: `50 GiB`
+pub fn human_size_long(bytes: u64) -> String {
+    let short = human_size(bytes);
+    let (number, unit) = short.split_at(short.len() - 1);
+    match unit {
+        "B" => format!("{} B", number),
+        _ => format!("{} {}iB", number, unit),
+    }
+}
+
+/// Renders `lsblk`, or `lsblk -f` when `filesystems` is set
+pub fn render_lsblk(disks: &[Disk], vfs: &Vfs, filesystems: bool) -> String {
+    let mut rows = Vec::new();
+    for (index, disk) in disks.iter().enumerate() {
+        let major = 8;
+        let minor = index as u64 * 16;
+        rows.push((disk.name.clone(), disk.path(), format!("{}:{}", major, minor), disk.bytes(), "disk"));
+        let count = disk.partitions.len();
+        for (position, partition) in disk.partitions.iter().enumerate() {
+            let branch = if position + 1 == count { "└─" } else { "├─" };
+            let path = disk.partition_path(partition.number);
+            let name = format!("{}{}", branch, path.trim_start_matches("/dev/"));
+            let devno = format!("{}:{}", major, minor + partition.number as u64);
+            rows.push((name, path, devno, partition.sectors() * SECTOR_SIZE, "part"));
+        }
+    }
+
+    let mountpoints = |path: &str| -> String {
+        vfs.mounts().iter().filter(|m| m.source == path).map(|m| m.target.as_str()).collect::<Vec<_>>().join("\n")
+    };
+    let width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(4).max(4);
+    let pad = |name: &str| format!("{}{}", name, " ".repeat(width - name.chars().count()));
+
+    let mut out = String::new();
+    if filesystems {
+        out.push_str(&format!("{} FSTYPE FSVER LABEL UUID                                 MOUNTPOINTS\n", pad("NAME")));
+        for (name, path, _, _, _) in &rows {
+            let (fstype, uuid) = vfs
+                .filesystem(path)
+                .map(|fs| (fs.fstype.as_str(), fs.uuid.as_str()))
+                .unwrap_or(("", ""));
+            let row = format!("{} {:<6} {:<5} {:<5} {:<36} {}", pad(name), fstype, "", "", uuid, mountpoints(path));
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+    } else {
+        out.push_str(&format!("{} MAJ:MIN RM   SIZE RO TYPE MOUNTPOINTS\n", pad("NAME")));
+        for (name, path, devno, bytes, kind) in &rows {
+            let row = format!("{} {:>7}  0 {:>6}  0 {:<4} {}", pad(name), devno, human_size(*bytes), kind, mountpoints(path));
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Renders `fdisk -l` for one disk
+pub fn render_fdisk_list(disk: &Disk) -> String {
+    let mut out = format!(
+        "Disk {}: {}, {} bytes, {} sectors\nDisk model: {}\nUnits: sectors of 1 * {} = {} bytes\nSector size (logical/physical): {} bytes / {} bytes\nI/O size (minimum/optimal): {} bytes / {} bytes\n",
+        disk.path(), human_size_long(disk.bytes()), disk.bytes(), disk.sectors, disk.model,
+        SECTOR_SIZE, SECTOR_SIZE, SECTOR_SIZE, SECTOR_SIZE, SECTOR_SIZE, SECTOR_SIZE,
+    );
+    let Some(label) = disk.label else {
+        return out;
+    };
+    out.push_str(&format!("Disklabel type: {}\nDisk identifier: {}\n", label, disk.identifier()));
+    if disk.partitions.is_empty() {
+        return out;
+    }
+
+    let width = disk.partition_path(disk.max_partitions()).len().max(6);
+    out.push('\n');
+    match label {
+        PartitionTable::Gpt => out.push_str(&format!("{:<width$}   Start       End   Sectors  Size Type\n", "Device")),
+        PartitionTable::Dos => out.push_str(&format!("{:<width$} Boot   Start       End   Sectors  Size Id Type\n", "Device")),
+    }
+    for partition in &disk.partitions {
+        let path = disk.partition_path(partition.number);
+        let size = human_size(partition.sectors() * SECTOR_SIZE);
+        match label {
+            PartitionTable::Gpt => out.push_str(&format!(
+                "{:<width$} {:>7} {:>9} {:>9} {:>5} {}\n",
+                path, partition.start, partition.end, partition.sectors(), size, partition.kind.name
+            )),
+            PartitionTable::Dos => out.push_str(&format!(
+                "{:<width$}      {:>7} {:>9} {:>9} {:>5} {:>2x} {}\n",
+                path, partition.start, partition.end, partition.sectors(), size, partition.kind.mbr_id, partition.kind.mbr_name
+            )),
+        }
+    }
+    out
+}
+
+/// Renders `blkid` for every formatted partition
+pub fn render_blkid(disks: &[Disk], vfs: &Vfs) -> String {
+    let mut out = String::new();
+    for disk in disks {
+        for partition in &disk.partitions {
+            let path = disk.partition_path(partition.number);
+            let partuuid = disk.partuuid(partition.number);
+            match vfs.filesystem(&path) {
+                Some(fs) => {
+                    let block = if fs.fstype == "ext4" { 4096 } else { 512 };
+                    out.push_str(&format!(
+                        "{}: UUID=\"{}\" BLOCK_SIZE=\"{}\" TYPE=\"{}\" PARTUUID=\"{}\"\n",
+                        path, fs.uuid, block, fs.fstype, partuuid
+                    ));
+                }
+                None => out.push_str(&format!("{}: PARTUUID=\"{}\"\n", path, partuuid)),
+            }
+        }
+    }
+    out
+}
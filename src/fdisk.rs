@@ -0,0 +1,281 @@
+use crate::frontend::Frontend;
+use crate::disk::{
+    human_size_long, parse_size, render_fdisk_list, Disk, PartitionTable, PartitionType, ALIGNMENT,
+    PARTITION_TYPES, SECTOR_SIZE,
+};
+
+/// Version string fdisk greets with
+const FDISK_VERSION: &str = "util-linux 2.40.2";
+
+/// Help text printed by `m`
+const HELP: &str = "
+Help:
+
+  Generic
+   d   delete a partition
+   l   list known partition types
+   n   add a new partition
+   p   print the partition table
+   t   change a partition type
+
+  Misc
+   m   print this menu
+
+  Save & Exit
+   w   write table to disk and exit
+   q   quit without saving changes
+
+  Create a new label
+   g   create a new empty GPT partition table
+   o   create a new empty MBR (DOS) partition table
+";
+
+/// Outcome of an fdisk session
+pub enum Outcome {
+    Written(Disk),
+    Quit,
+}
+
+/// Runs an interactive fdisk session on a copy of `disk`
+pub fn run(disk: &Disk, frontend: &mut dyn Frontend) -> Outcome {
+    let mut disk = disk.clone();
+    frontend.println(&format!("\nWelcome to fdisk ({}).", FDISK_VERSION));
+    frontend.println("Changes will remain in memory only, until you decide to write them.");
+    frontend.println("Be careful before using the write command.\n");
+
+    if disk.label.is_none() {
+        disk.create_label(PartitionTable::Dos);
+        frontend.println("Device does not contain a recognized partition table.");
+        frontend.println(&format!("Created a new DOS (MBR) disklabel with disk identifier {}.", disk.identifier()));
+    }
+
+    loop {
+        frontend.println("");
+        let Ok(line) = frontend.read_line("Command (m for help): ") else {
+            frontend.println("");
+            return Outcome::Quit;
+        };
+        match line.trim() {
+            "" => {}
+            "m" => frontend.print(HELP),
+            "p" => frontend.println(&render_fdisk_list(&disk)),
+            "l" => list_types(&disk, frontend),
+            "g" => {
+                disk.create_label(PartitionTable::Gpt);
+                frontend.println(&format!("Created a new GPT disklabel (GUID: {}).", disk.identifier()));
+            }
+            "o" => {
+                disk.create_label(PartitionTable::Dos);
+                frontend.println(&format!("Created a new DOS (MBR) disklabel with disk identifier {}.", disk.identifier()));
+            }
+            "n" => new_partition(&mut disk, frontend),
+            "d" => delete_partition(&mut disk, frontend),
+            "t" => change_type(&mut disk, frontend),
+            "w" => {
+                frontend.println("The partition table has been altered.");
+                frontend.println("Calling ioctl() to re-read partition table.");
+                frontend.println("Syncing disks.");
+                return Outcome::Written(disk);
+            }
+            "q" => return Outcome::Quit,
+            other => frontend.println(&format!("{}: unknown command", other)),
+        }
+    }
+}
+
+/// Asks a question until the answer parses, returning `None` on end of input
+fn ask<T>(frontend: &mut dyn Frontend, prompt: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    loop {
+        let answer = frontend.read_line(prompt).ok()?;
+        match parse(answer.trim()) {
+            Some(value) => return Some(value),
+            None => frontend.println("Value out of range."),
+        }
+    }
+}
+
+/// Asks for an existing partition number, selecting the only one automatically
+fn select_partition(disk: &Disk, frontend: &mut dyn Frontend) -> Option<u32> {
+    let numbers: Vec<u32> = disk.partitions.iter().map(|p| p.number).collect();
+    match numbers.as_slice() {
+        [] => {
+            frontend.println("No partition is defined yet!");
+            None
+        }
+        [only] => {
+            frontend.println(&format!("Selected partition {}", only));
+            Some(*only)
+        }
+        _ => {
+            let last = *numbers.last().unwrap();
+            let list = numbers.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+            let prompt = format!("Partition number ({}, default {}): ", list, last);
+            ask(frontend, &prompt, |answer| match answer {
+                "" => Some(last),
+                text => text.parse().ok().filter(|n| numbers.contains(n)),
+            })
+        }
+    }
+}
+
+/// Handles `n`: number, first sector and last sector prompts
+fn new_partition(disk: &mut Disk, frontend: &mut dyn Frontend) {
+    let Some(default_number) = disk.next_number() else {
+        frontend.println("All partitions are already in use.");
+        return;
+    };
+    let free = disk.free_ranges();
+    let Some(&(first_free, _)) = free.first() else {
+        frontend.println("No free sectors available.");
+        return;
+    };
+
+    if disk.label == Some(PartitionTable::Dos) {
+        let used = disk.partitions.len();
+        frontend.println("Partition type");
+        frontend.println(&format!("   p   primary ({} primary, 0 extended, {} free)", used, 4 - used));
+        frontend.println("   e   extended (container for logical partitions)");
+        let Some(kind) = ask(frontend, "Select (default p): ", |answer| match answer {
+            "" | "p" => Some(true),
+            "e" => Some(false),
+            _ => None,
+        }) else {
+            return;
+        };
+        if !kind {
+            frontend.println("Extended partitions are not used by the Arch install; creating a primary partition instead.");
+        }
+    }
+
+    let max = disk.max_partitions();
+    let prompt = format!("Partition number ({}-{}, default {}): ", default_number, max, default_number);
+    let Some(number) = ask(frontend, &prompt, |answer| match answer {
+        "" => Some(default_number),
+        text => text.parse().ok().filter(|n| (1..=max).contains(n)),
+    }) else {
+        return;
+    };
+    if disk.partition(number).is_some() {
+        frontend.println(&format!("Partition {} is already defined.  Delete it before re-adding it.", number));
+        return;
+    }
+
+    let last_usable = disk.last_usable();
+    let prompt = format!("First sector ({}-{}, default {}): ", first_free, last_usable, first_free);
+    let Some(start) = ask(frontend, &prompt, |answer| match answer {
+        "" => Some(first_free),
+        text => text.parse().ok().filter(|s| disk.free_range_at(*s).is_some()),
+    }) else {
+        return;
+    };
+
+    let (_, free_end) = disk.free_range_at(start).expect("start lies in free space");
+    let aligned_end = ((free_end + 1) / ALIGNMENT * ALIGNMENT).saturating_sub(1);
+    let default_end = if aligned_end > start { aligned_end } else { free_end };
+    let prompt = format!(
+        "Last sector, +/-sectors or +/-size{{K,M,G,T,P}} ({}-{}, default {}): ",
+        start, free_end, default_end
+    );
+    let Some(end) = ask(frontend, &prompt, |answer| {
+        parse_last_sector(answer, start, free_end, default_end).filter(|end| (start..=free_end).contains(end))
+    }) else {
+        return;
+    };
+
+    match disk.add_partition(number, start, end) {
+        Ok(()) => {
+            let bytes = (end - start + 1) * SECTOR_SIZE;
+            let kind = disk.partition(number).map_or("Linux", |p| p.kind.label_name(disk.label));
+            frontend.println(&format!("\nCreated a new partition {} of type '{}' and of size {}.", number, kind, human_size_long(bytes)));
+        }
+        Err(err) => frontend.println(&err.to_string()),
+    }
+}
+
+/// Parses fdisk's last-sector syntax: empty, absolute, `+sectors`, `+size` or `-size`
+fn parse_last_sector(answer: &str, start: u64, free_end: u64, default_end: u64) -> Option<u64> {
+    let to_sectors = |text: &str| -> Option<u64> {
+        let has_unit = text.ends_with(|c: char| c.is_ascii_alphabetic());
+        let value = parse_size(text)?;
+        Some(if has_unit { value / SECTOR_SIZE } else { value })
+    };
+    match answer {
+        "" => Some(default_end),
+        text if text.starts_with('+') => {
+            let sectors = to_sectors(&text[1..])?;
+            let end = start.checked_add(sectors)?.checked_sub(1)?;
+            // Sizes snap to the alignment grid, like fdisk does
+            let aligned = if text.ends_with(|c: char| c.is_ascii_alphabetic()) {
+                (end + 1) / ALIGNMENT * ALIGNMENT - 1
+            } else {
+                end
+            };
+            Some(aligned.max(start))
+        }
+        text if text.starts_with('-') => free_end.checked_sub(to_sectors(&text[1..])?),
+        text => text.parse().ok(),
+    }
+}
+
+/// Handles `d`
+fn delete_partition(disk: &mut Disk, frontend: &mut dyn Frontend) {
+    if let Some(number) = select_partition(disk, frontend) {
+        match disk.delete_partition(number) {
+            Ok(()) => frontend.println(&format!("\nPartition {} has been deleted.", number)),
+            Err(err) => frontend.println(&err.to_string()),
+        }
+    }
+}
+
+/// Handles `t`, accepting list numbers, aliases, GUIDs or MBR hex codes
+fn change_type(disk: &mut Disk, frontend: &mut dyn Frontend) {
+    let Some(number) = select_partition(disk, frontend) else {
+        return;
+    };
+    let old = disk.partition(number).map_or("Linux", |p| p.kind.label_name(disk.label));
+    let kind = loop {
+        let Ok(answer) = frontend.read_line("Partition type or alias (type L to list all): ") else {
+            return;
+        };
+        match answer.trim() {
+            "L" => list_types(disk, frontend),
+            "" => {}
+            text => match lookup_type(disk, text) {
+                Some(kind) => break kind,
+                None => frontend.println(&format!("Failed to parse '{}' partition type.", text)),
+            },
+        }
+    };
+    match disk.set_type(number, kind) {
+        Ok(()) => frontend.println(&format!("Changed type of partition '{}' to '{}'.", old, kind.label_name(disk.label))),
+        Err(err) => frontend.println(&err.to_string()),
+    }
+}
+
+/// Resolves a type answer against the current label
+fn lookup_type(disk: &Disk, text: &str) -> Option<&'static PartitionType> {
+    let gpt = disk.label == Some(PartitionTable::Gpt);
+    PARTITION_TYPES.iter().find(|t| {
+        t.alias == text
+            || t.guid.eq_ignore_ascii_case(text)
+            || (gpt && text.parse() == Ok(t.gpt_number))
+            || (!gpt && t.mbr_id != 0 && u8::from_str_radix(text, 16) == Ok(t.mbr_id))
+    })
+}
+
+/// Handles `l` and `L`
+fn list_types(disk: &Disk, frontend: &mut dyn Frontend) {
+    let gpt = disk.label == Some(PartitionTable::Gpt);
+    for kind in PARTITION_TYPES {
+        if gpt {
+            frontend.println(&format!("{:>3} {:<24} {}", kind.gpt_number, kind.name, kind.guid));
+        } else if kind.mbr_id != 0 {
+            frontend.println(&format!("{:>3x}  {}", kind.mbr_id, kind.mbr_name));
+        }
+    }
+    frontend.println("\nAliases:");
+    for kind in PARTITION_TYPES.iter().filter(|k| gpt || k.mbr_id != 0) {
+        let code = if gpt { kind.guid.to_string() } else { format!("{:x}", kind.mbr_id) };
+        frontend.println(&format!("   {:<8} - {}", kind.alias, code));
+    }
+}
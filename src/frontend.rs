@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+use crate::input_handler::{self, parse_choice, Completer};
+use crate::pacing::{Pacer, Speed};
+use crate::utils::{clear_screen, create_progress_bar};
+
+/// Everything the simulator shows to, or asks of, the person taking the lesson
+pub trait Frontend {
+    /// Writes text as it is, like `print!`
+    fn print(&mut self, text: &str);
+
+    /// Writes text followed by a newline
+    fn println(&mut self, text: &str) {
+        self.print(text);
+        self.print("\n");
+    }
+
+    fn clear_screen(&mut self);
+
+    /// Waits so the learner can read what was printed
+    fn pause(&mut self, secs: u64);
+
+    /// Shows a progress bar filling over `ticks` seconds, then `done`
+    fn progress(&mut self, ticks: u64, message: &str, done: &str);
+
+    /// Time since the session started
+    fn elapsed(&self) -> Duration;
+
+    /// Asks to pick one of `items`, returning its index. `items` must not be empty.
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> usize;
+
+    /// Asks for a line of text, with an optional default for an empty answer
+    fn input(&mut self, prompt: &str, default: Option<String>) -> String;
+
+    /// Asks for a password without showing it. `UnexpectedEof` means the
+    /// user is done (Ctrl-D).
+    fn password(&mut self, prompt: &str) -> io::Result<String>;
+
+    /// Reads a shell command line. `UnexpectedEof` means the user is done (Ctrl-D).
+    fn read_command(&mut self, prompt: &str, completer: &dyn Completer) -> io::Result<String>;
+
+    /// Reads an answer to an interactive program such as fdisk
+    fn read_line(&mut self, prompt: &str) -> io::Result<String>;
+}
+
+/// The real terminal: line editor, dialogs and progress bars
+pub struct Terminal {
+    pacer: Pacer,
+    started: Instant,
+}
+
+impl Terminal {
+    pub fn new(pacer: Pacer) -> Self {
+        Terminal { pacer, started: Instant::now() }
+    }
+}
+
+impl Frontend for Terminal {
+    fn print(&mut self, text: &str) {
+        print!("{}", text);
+    }
+
+    fn clear_screen(&mut self) {
+        clear_screen();
+    }
+
+    fn pause(&mut self, secs: u64) {
+        self.pacer.pause(secs);
+    }
+
+    fn progress(&mut self, ticks: u64, message: &str, done: &str) {
+        if self.pacer.speed() == Speed::Instant {
+            println!("{}", done);
+            return;
+        }
+        let pb = create_progress_bar(ticks);
+        for i in 0..ticks {
+            pb.set_position(i);
+            pb.set_message(message.to_string());
+            self.pacer.pause(1);
+        }
+        pb.finish_with_message(done.to_string());
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> usize {
+        input_handler::select(prompt, items, default)
+    }
+
+    fn input(&mut self, prompt: &str, default: Option<String>) -> String {
+        input_handler::input(prompt, default)
+    }
+
+    fn password(&mut self, prompt: &str) -> io::Result<String> {
+        input_handler::read_password(prompt)
+    }
+
+    fn read_command(&mut self, prompt: &str, completer: &dyn Completer) -> io::Result<String> {
+        input_handler::read_input_with_history(prompt, completer)
+    }
+
+    fn read_line(&mut self, prompt: &str) -> io::Result<String> {
+        input_handler::read_input(prompt)
+    }
+}
+
+/// Answers from a list and output collected in a string, for tests and for
+/// running fast-forwarded steps out of sight. Prompts and answers are written
+/// to the output as a scripted terminal session would show them; pauses and
+/// progress bars take no real time, but advance a virtual clock so reported
+/// times are the same on every machine.
+#[derive(Debug, Default)]
+pub struct Memory {
+    inputs: VecDeque<String>,
+    output: String,
+    elapsed: Duration,
+}
+
+impl Memory {
+    /// Answers are consumed in order, by commands and dialogs alike. Dialogs
+    /// accept the same answers as a `--script` file; a tab in a shell command
+    /// completes like the Tab key.
+    pub fn new<S: Into<String>>(inputs: impl IntoIterator<Item = S>) -> Self {
+        Memory { inputs: inputs.into_iter().map(Into::into).collect(), ..Memory::default() }
+    }
+
+    /// Everything printed so far
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Answers not consumed yet
+    pub fn remaining(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn answer(&mut self, prompt: &str, echo: bool) -> io::Result<String> {
+        self.print(prompt);
+        let answer = self.inputs.pop_front().ok_or(io::ErrorKind::UnexpectedEof)?;
+        let shown = if echo { answer.clone() } else { String::new() };
+        self.println(&shown);
+        Ok(answer)
+    }
+
+    /// Dialogs have no way to signal the end of input
+    fn dialog_answer(&mut self, prompt: &str, echo: bool) -> String {
+        self.answer(prompt, echo).unwrap_or_else(|_| panic!("no input left for {:?}\n{}", prompt, self.output))
+    }
+}
+
+impl Frontend for Memory {
+    fn print(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn clear_screen(&mut self) {}
+
+    fn pause(&mut self, secs: u64) {
+        self.elapsed += Duration::from_secs(secs);
+    }
+
+    fn progress(&mut self, ticks: u64, _message: &str, done: &str) {
+        self.pause(ticks);
+        self.println(done);
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> usize {
+        assert!(!items.is_empty(), "select {:?} has no items to choose from", prompt);
+        loop {
+            let answer = self.dialog_answer(&format!("{} [{}]: ", prompt, items[default]), true);
+            match parse_choice(items, &answer, default) {
+                Some(index) => return index,
+                None => self.println(&format!("Choose one of: {}", items.join(", "))),
+            }
+        }
+    }
+
+    fn input(&mut self, prompt: &str, default: Option<String>) -> String {
+        let answer = match &default {
+            Some(default) => self.dialog_answer(&format!("{} [{}]: ", prompt, default), true),
+            None => self.dialog_answer(&format!("{}: ", prompt), true),
+        };
+        match default {
+            Some(default) if answer.is_empty() => default,
+            _ => answer,
+        }
+    }
+
+    fn password(&mut self, prompt: &str) -> io::Result<String> {
+        self.answer(prompt, false)
+    }
+
+    fn read_command(&mut self, prompt: &str, completer: &dyn Completer) -> io::Result<String> {
+        self.print(prompt);
+        let mut answer = self.inputs.pop_front().ok_or(io::ErrorKind::UnexpectedEof)?;
+        if answer.contains('\t') {
+            // Typed into the line editor; only the finished line is shown
+            let keys = format!("{}\r", answer);
+            answer = input_handler::edit(&mut keys.as_bytes(), &mut io::sink(), prompt, &[], Some(completer))?;
+        }
+        self.println(&answer);
+        Ok(answer)
+    }
+
+    fn read_line(&mut self, prompt: &str) -> io::Result<String> {
+        self.answer(prompt, true)
+    }
+}
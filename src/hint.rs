@@ -0,0 +1,75 @@
+use crate::shell;
+
+/// Reveals before the whole command is shown: program, flags, full line
+pub const LEVELS: u32 = 3;
+
+/// How much of `command` a hint shows at `level`, from 1 to `LEVELS`
+pub fn reveal(command: &str, level: u32) -> String {
+    match level {
+        0 => String::new(),
+        1 => command.split_whitespace().next().unwrap_or_default().to_string(),
+        2 => outline(command),
+        _ => command.to_string(),
+    }
+}
+
+/// The command with programs and flags kept and everything else blanked,
+/// e.g. `mount --mkdir … …` or `grub-install --target=… --efi-directory=…`
+fn outline(command: &str) -> String {
+    let Ok(script) = shell::parse(command) else {
+        return reveal(command, 1);
+    };
+    let parts: Vec<String> = script
+        .commands(&|_| None)
+        .iter()
+        .map(|line| {
+            let mut words: Vec<String> = line
+                .words
+                .iter()
+                .enumerate()
+                .map(|(index, word)| match word.split_once('=') {
+                    _ if index == 0 => word.clone(),
+                    Some((flag, _)) if flag.starts_with('-') => format!("{}=…", flag),
+                    _ if word.starts_with('-') => word.clone(),
+                    _ => "…".to_string(),
+                })
+                .collect();
+            if let Some(redirect) = &line.redirect {
+                words.push(if redirect.append { ">> …" } else { "> …" }.to_string());
+            }
+            words.join(" ")
+        })
+        .collect();
+    parts.join("; ")
+}
+
+/// What keeps `typed` from being `expected`, word by word, e.g. "`mount` is
+/// close: it also needs `/dev/sdz2`; `/dev/sdz3` does not belong here."
+pub fn explain(expected: &str, typed: &str) -> String {
+    let wanted: Vec<&str> = expected.split_whitespace().collect();
+    let given: Vec<&str> = typed.split_whitespace().collect();
+    let program = wanted.first().copied().unwrap_or_default();
+    match given.first() {
+        None => format!("Nothing was typed; this step runs `{}`.", program),
+        Some(&other) if other != program => format!("This step runs `{}`, not `{}`.", program, other),
+        Some(_) => {
+            let quote = |words: Vec<&&str>| words.iter().map(|word| format!("`{}`", word)).collect::<Vec<_>>().join(", ");
+            let missing = quote(wanted.iter().filter(|word| !given.contains(word)).collect());
+            let extra = quote(given.iter().filter(|word| !wanted.contains(word)).collect());
+            let mut problems = Vec::new();
+            if !missing.is_empty() {
+                problems.push(format!("it also needs {}", missing));
+            }
+            if !extra.is_empty() {
+                problems.push(format!("{} does not belong here", extra));
+            }
+            if !problems.is_empty() {
+                format!("`{}` is close: {}.", program, problems.join("; "))
+            } else if wanted != given {
+                format!("`{}` has the right words, but not in the right order.", program)
+            } else {
+                "The command is right, but it did not leave the machine as the step needs; read the error it printed.".to_string()
+            }
+        }
+    }
+}
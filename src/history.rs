@@ -0,0 +1,163 @@
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Oldest entries are dropped past this many lines, unless configured otherwise
+pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+static MAX_ENTRIES: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_ENTRIES);
+
+/// Setting this environment variable (to anything) turns persistence off
+const DISABLE_VAR: &str = "ARCHTRY_NO_HISTORY";
+
+static PERSIST: AtomicBool = AtomicBool::new(true);
+
+/// This session's history, loaded from disk on first use
+static SESSION: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Keeps history in memory only, for this run
+pub fn disable() {
+    PERSIST.store(false, Ordering::Relaxed);
+}
+
+/// Keeps at most `entries` commands
+pub fn set_max_entries(entries: usize) {
+    MAX_ENTRIES.store(entries, Ordering::Relaxed);
+}
+
+fn persistent() -> bool {
+    PERSIST.load(Ordering::Relaxed) && std::env::var_os(DISABLE_VAR).is_none()
+}
+
+/// `$XDG_STATE_HOME/archtry/history`, or `~/.local/state/archtry/history`
+pub fn path() -> Option<PathBuf> {
+    dirs::state_dir().map(|dir| dir.join("archtry").join("history"))
+}
+
+/// Commands entered so far, oldest first
+pub fn entries() -> Vec<String> {
+    with_session(|entries| entries.clone())
+}
+
+/// Records a command, moving an earlier copy of it to the end. If the file
+/// cannot be written, history stays in memory for the rest of the run.
+pub fn add(command: &str) -> io::Result<()> {
+    with_session(|entries| push(entries, command));
+    if !persistent() {
+        return Ok(());
+    }
+    save(command).inspect_err(|_| disable())
+}
+
+fn with_session<T>(f: impl FnOnce(&mut Vec<String>) -> T) -> T {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    f(session.get_or_insert_with(|| if persistent() { load().unwrap_or_default() } else { Vec::new() }))
+}
+
+/// Forgets the history, on disk and in memory
+pub fn clear() -> io::Result<()> {
+    *SESSION.lock().unwrap_or_else(|e| e.into_inner()) = Some(Vec::new());
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn push(entries: &mut Vec<String>, command: &str) {
+    entries.retain(|entry| entry != command);
+    entries.push(command.to_string());
+    let max = MAX_ENTRIES.load(Ordering::Relaxed);
+    if entries.len() > max {
+        entries.drain(..entries.len() - max);
+    }
+}
+
+fn load() -> io::Result<Vec<String>> {
+    let Some(path) = path() else {
+        return Ok(Vec::new());
+    };
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut file = open(&path)?;
+    lock(&file, libc::LOCK_SH)?;
+    read_entries(&mut file)
+}
+
+/// Merges the command into the file under an exclusive lock, so concurrent
+/// sessions never interleave partial writes
+fn save(command: &str) -> io::Result<()> {
+    let path = path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    if let Some(dir) = path.parent() {
+        create_dir(dir)?;
+    }
+    let mut file = open(&path)?;
+    lock(&file, libc::LOCK_EX)?;
+    let mut entries = read_entries(&mut file)?;
+    push(&mut entries, command);
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    let mut text = entries.join("\n");
+    text.push('\n');
+    file.write_all(text.as_bytes())
+}
+
+fn read_entries(file: &mut File) -> io::Result<Vec<String>> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    Ok(text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+}
+
+/// Creates the state directory private to the user, refusing one that is a
+/// symlink or belongs to someone else
+pub(crate) fn create_dir(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    let metadata = dir.symlink_metadata()?;
+    if !metadata.is_dir() || metadata.uid() != euid() {
+        return Err(unsafe_path(dir));
+    }
+    Ok(())
+}
+
+/// Opens the history file without following symlinks, checking that it is a
+/// regular file owned by the user
+fn open(path: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.uid() != euid() {
+        return Err(unsafe_path(path));
+    }
+    Ok(file)
+}
+
+/// Blocks until the advisory lock is held; it is released when the file closes
+fn lock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    // SAFETY: flock only reads the descriptor, which `file` keeps open
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn euid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
+}
+
+fn unsafe_path(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("{}: not a private file owned by this user", path.display()))
+}
@@ -1,197 +1,681 @@
-use std::fs::{File, OpenOptions};
-use std::io::{self, Write, Read, BufReader, BufRead};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
 use std::path::Path;
-use termios::{Termios, tcsetattr, ICANON, ECHO, TCSANOW, VMIN, VTIME};
+use termios::{Termios, tcsetattr, ICANON, ECHO, ISIG, TCSANOW, VMIN, VTIME};
 use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+use signal_hook::iterator::Signals;
+use crate::history;
+use crate::utils::after_last;
+use colored::Colorize;
+use dialoguer::{Input, Select};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-const LOG_PATH: &str = "/tmp/archtry-log.txt";
-
-/// Initializes terminal in raw mode
-pub fn init_raw_mode() -> io::Result<Termios> {
-    let stdin = io::stdin();
-    let fd = stdin.as_raw_fd();
-    let original = Termios::from_fd(fd)?;
-    let mut new_termios = original;
-    
-    // Disable canonical mode and echo
-    new_termios.c_lflag &= !(ICANON | ECHO);
-    new_termios.c_cc[VMIN] = 1;  // Minimum chars to read
-    new_termios.c_cc[VTIME] = 0; // Timeout in deciseconds
-    tcsetattr(fd, TCSANOW, &new_termios)?;
-    
-    Ok(original)
+/// Supplies Tab completions for the word before the cursor
+pub trait Completer {
+    /// Returns the byte offset where the word starts and the candidates that extend it
+    fn complete(&self, line: &str, cursor: usize) -> (usize, Vec<String>);
 }
 
-/// Restores terminal to original settings
-pub fn restore_terminal(original: &Termios) -> io::Result<()> {
-    let stdin = io::stdin();
-    tcsetattr(stdin.as_raw_fd(), TCSANOW, original)?;
+/// Terminal settings from before the program touched them, restored on exit
+/// and on fatal signals
+static STARTUP: Mutex<Option<Termios>> = Mutex::new(None);
+
+/// Signals that end the program; the terminal is restored before exiting
+const FATAL_SIGNALS: [i32; 4] = [SIGHUP, SIGINT, SIGQUIT, SIGTERM];
+
+/// Remembers the terminal's settings and restores them if the process panics
+/// or is killed by a signal. Call once, before anything changes the terminal.
+pub fn install_terminal_guard() -> io::Result<()> {
+    if let Ok(original) = Termios::from_fd(io::stdin().as_raw_fd()) {
+        *STARTUP.lock().unwrap_or_else(|e| e.into_inner()) = Some(original);
+    }
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_startup();
+        report(info);
+    }));
+    let mut signals = Signals::new(FATAL_SIGNALS)?;
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            exit(128 + signal);
+        }
+    });
     Ok(())
 }
 
-/// Reads command history from log file
-fn read_history() -> io::Result<Vec<String>> {
-    if Path::new(LOG_PATH).exists() {
-        let file = File::open(LOG_PATH)?;
-        let reader = BufReader::new(file);
-        Ok(reader.lines().map_while(Result::ok).collect())
-    } else {
-        Ok(Vec::new())
+/// Restores the terminal as it was at startup, shows the cursor and exits.
+/// Use instead of `std::process::exit`, which skips destructors.
+pub fn exit(code: i32) -> ! {
+    restore_startup();
+    std::process::exit(code)
+}
+
+fn restore_startup() {
+    if let Some(original) = *STARTUP.lock().unwrap_or_else(|e| e.into_inner()) {
+        let _ = tcsetattr(io::stdin().as_raw_fd(), TCSANOW, &original);
+    }
+    // Menus hide the cursor while they are open
+    if io::stdout().is_terminal() {
+        print!("\x1B[?25h");
+        let _ = io::stdout().flush();
     }
 }
 
-/// Logs command to history file
-pub fn log_command(command: &str) -> io::Result<()> {
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(LOG_PATH)?;
-    
-    writeln!(file, "{}", command)
+/// Raw mode for the line editor; the previous settings come back when this is
+/// dropped, including on early returns and panics
+struct RawMode {
+    original: Termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let fd = io::stdin().as_raw_fd();
+        let original = Termios::from_fd(fd)?;
+        let mut raw = original;
+
+        // Disable canonical mode, echo and signal keys; Ctrl-C is handled by the editor
+        raw.c_lflag &= !(ICANON | ECHO | ISIG);
+        raw.c_cc[VMIN] = 1;  // Minimum chars to read
+        raw.c_cc[VTIME] = 0; // Timeout in deciseconds
+        tcsetattr(fd, TCSANOW, &raw)?;
+
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = tcsetattr(io::stdin().as_raw_fd(), TCSANOW, &self.original);
+    }
 }
 
-/// Computes the visible length of a string (ignoring ANSI escape sequences)
+/// Computes the display width of a string, ignoring ANSI escape sequences and
+/// counting wide (East Asian, emoji) characters as two columns
 fn visible_length(s: &str) -> usize {
-    let mut len = 0;
+    let mut visible = String::new();
     let mut in_escape = false;
     for c in s.chars() {
         if c == '\x1B' {
             in_escape = true;
         }
         if !in_escape {
-            len += 1;
+            visible.push(c);
         }
         if in_escape && c == 'm' {
             in_escape = false;
         }
     }
-    len
+    visible.width()
 }
 
 /// Redraws current line with cursor at correct position
-fn redraw_line(input: &str, cursor_pos: usize, prompt: &str) -> io::Result<()> {
+fn redraw_line(out: &mut impl Write, input: &str, cursor_pos: usize, prompt: &str) -> io::Result<()> {
     // Clear line and redraw prompt and input
-    print!("\r\x1B[K{}{}", prompt, input);
+    write!(out, "\r\x1B[K{}{}", prompt, input)?;
     
-    // Move cursor to correct position using visible length
-    let visible_prompt_len = visible_length(prompt);
-    let total_pos = visible_prompt_len + cursor_pos;
-    print!("\r\x1B[{}C", total_pos);
+    // Move cursor to correct position using display width
+    let total_pos = visible_length(prompt) + input[..cursor_pos].width();
+    write!(out, "\r")?;
+    if total_pos > 0 {
+        write!(out, "\x1B[{}C", total_pos)?;
+    }
     
-    io::stdout().flush()
+    out.flush()
 }
 
-/// Reads user input with history support
-pub fn read_input_with_history(prompt: &str) -> io::Result<String> {
-    // Save and configure terminal settings
-    let original_term = init_raw_mode()?;
-    
-    // Load command history
-    let history = read_history()?;
-    let mut history_index = history.len(); // Start at the end
-    let mut input = String::new();
-    let mut cursor_pos = 0;
-    let mut current_input_before_history = String::new(); // To save input before history navigation
+/// Applies Tab completion to `input`, returning the new cursor position
+fn complete(out: &mut impl Write, input: &mut String, cursor_pos: usize, completer: &dyn Completer, double_tab: bool, prompt: &str) -> io::Result<usize> {
+    let (start, candidates) = completer.complete(input, cursor_pos);
+    let typed = cursor_pos - start;
+    match candidates.as_slice() {
+        [] => write!(out, "\x07")?,
+        [only] => {
+            // Directories stay open so the next Tab can descend into them
+            let suffix = if only.ends_with('/') { "" } else { " " };
+            input.replace_range(start..cursor_pos, &format!("{}{}", only, suffix));
+            return Ok(start + only.len() + suffix.len());
+        }
+        _ => {
+            let common = common_prefix(&candidates);
+            if common.len() > typed {
+                input.replace_range(start..cursor_pos, common);
+                return Ok(start + common.len());
+            }
+            if double_tab {
+                writeln!(out)?;
+                print_candidates(out, &candidates)?;
+                write!(out, "{}", prompt)?;
+            } else {
+                write!(out, "\x07")?;
+            }
+        }
+    }
+    Ok(cursor_pos)
+}
+
+/// Longest prefix shared by all candidates
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = &candidates[0];
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(len);
+    }
+    &first[..len]
+}
+
+/// Lists candidates in columns, showing only the last path component like bash
+fn print_candidates(out: &mut impl Write, candidates: &[String]) -> io::Result<()> {
+    let names: Vec<&str> = candidates
+        .iter()
+        .map(|c| match c.trim_end_matches('/').rfind('/') {
+            Some(index) => &c[index + 1..],
+            None => c.as_str(),
+        })
+        .collect();
+    let width = names.iter().map(|n| n.width()).max().unwrap_or(0) + 2;
+    let columns = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(80usize);
+    let per_row = (columns / width).max(1);
+    for row in names.chunks(per_row) {
+        let line: String = row.iter().map(|name| format!("{}{}", name, " ".repeat(width - name.width()))).collect();
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
 
+/// Reads user input with history support and Tab completion
+pub fn read_input_with_history(prompt: &str, completer: &dyn Completer) -> io::Result<String> {
+    let input = edit_line(prompt, &history::entries(), Some(completer))?;
+    if !input.is_empty() && interactive() && let Err(err) = history::add(&input) {
+        eprintln!("{}", format!("Warning: history not saved: {}", err).yellow());
+    }
+    Ok(input)
+}
+
+/// Reads a line for a sub-prompt (e.g. fdisk) without touching the history
+pub fn read_input(prompt: &str) -> io::Result<String> {
+    edit_line(prompt, &[], None)
+}
+
+/// Answers read from a `--script` file instead of the keyboard
+static SCRIPT: Mutex<Option<BufReader<File>>> = Mutex::new(None);
+
+/// Takes every command and dialog answer from `path`, one per line, in the
+/// order the simulation asks for them. Lines starting with `#` are skipped;
+/// an empty line accepts a dialog's default.
+pub fn use_script(path: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    *SCRIPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(BufReader::new(file));
+    Ok(())
+}
+
+/// Whether input comes from someone at a terminal, rather than a script or a pipe
+pub fn interactive() -> bool {
+    SCRIPT.lock().unwrap_or_else(|e| e.into_inner()).is_none() && io::stdin().is_terminal()
+}
+
+/// Reads the next line from the script or from standard input, without the
+/// newline. Returns `UnexpectedEof` once the input runs out.
+fn next_line() -> io::Result<String> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = match SCRIPT.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(script) => script.read_line(&mut line)?,
+            None => io::stdin().read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if !line.starts_with('#') {
+            return Ok(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+    }
+}
+
+/// Line-based prompt for when there is no terminal to edit on. The answer is
+/// echoed so the output reads like a session transcript.
+fn read_plain(prompt: &str, echo: bool) -> io::Result<String> {
     print!("{}", prompt);
     io::stdout().flush()?;
+    let line = next_line()?;
+    println!("{}", if echo { line.as_str() } else { "" });
+    Ok(line)
+}
 
-    let mut stdin = io::stdin();
-    let mut buf = [0; 1];
+/// Dialogs cannot be skipped, so running out of scripted answers ends the run
+fn read_answer(prompt: &str, echo: bool) -> String {
+    read_plain(prompt, echo).unwrap_or_else(|_| {
+        println!("\n{}", "Input ended before the simulation finished.".red());
+        exit(1)
+    })
+}
 
+/// Asks the user to pick one of `items`; scripts answer as `parse_choice` reads.
+/// Panics if `items` is empty, as there would be nothing to pick.
+pub fn select(prompt: &str, items: &[&str], default: usize) -> usize {
+    assert!(!items.is_empty(), "select {:?} has no items to choose from", prompt);
+    if interactive() {
+        return Select::new().with_prompt(prompt).items(items).default(default).interact().unwrap_or(default);
+    }
     loop {
-        if stdin.read(&mut buf)? == 0 {
-            break;
+        let answer = read_answer(&format!("{} [{}]: ", prompt, items[default]), true);
+        match parse_choice(items, &answer, default) {
+            Some(index) => return index,
+            None => println!("{}", format!("Choose one of: {}", items.join(", ")).red()),
         }
+    }
+}
 
-        match buf[0] {
-            // Enter key
-            10 | 13 => {
-                if !input.is_empty() {
-                    log_command(&input)?;
-                }
-                println!();
-                break;
+/// Reads a typed answer to a selection: an item's text (case-insensitive), its
+/// number counting from 1, or nothing for the default
+pub fn parse_choice(items: &[&str], answer: &str, default: usize) -> Option<usize> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Some(default);
+    }
+    items.iter().position(|item| item.eq_ignore_ascii_case(answer)).or_else(|| match answer.parse::<usize>() {
+        Ok(number) if (1..=items.len()).contains(&number) => Some(number - 1),
+        _ => None,
+    })
+}
+
+/// Asks for a line of text, with an optional default for an empty answer
+pub fn input(prompt: &str, default: Option<String>) -> String {
+    if interactive() {
+        let mut input = Input::<String>::new().with_prompt(prompt);
+        if let Some(default) = default.clone() {
+            input = input.default(default);
+        }
+        return input.interact().unwrap_or_else(|_| default.unwrap_or_default());
+    }
+    let answer = match &default {
+        Some(default) => read_answer(&format!("{} [{}]: ", prompt, default), true),
+        None => read_answer(&format!("{}: ", prompt), true),
+    };
+    match default {
+        Some(default) if answer.is_empty() => default,
+        _ => answer,
+    }
+}
+
+/// Reads a password without echoing it. Ctrl-D, or the end of a script,
+/// is `UnexpectedEof`.
+pub fn read_password(prompt: &str) -> io::Result<String> {
+    if interactive() {
+        print!("{}", prompt);
+        let _ = io::stdout().flush();
+        return rpassword::read_password();
+    }
+    read_plain(prompt, false)
+}
+
+/// Key press decoded from the terminal's byte stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    /// Control plus a letter, e.g. `Ctrl('a')`
+    Ctrl(char),
+    /// Escape followed by a character, e.g. `Alt('b')`
+    Alt(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Escape,
+    Unknown,
+}
+
+/// Reads one byte, returning `None` at end of input
+fn read_byte(stdin: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut buf = [0; 1];
+    Ok((stdin.read(&mut buf)? == 1).then_some(buf[0]))
+}
+
+/// Reads one byte if it arrives within 100ms, telling a lone Escape from a sequence
+fn read_byte_soon(stdin: &mut impl Read) -> io::Result<Option<u8>> {
+    let fd = io::stdin().as_raw_fd();
+    // Without a terminal there is no timing to go by: the bytes are already there
+    let Ok(raw) = Termios::from_fd(fd) else {
+        return read_byte(stdin);
+    };
+    let mut waiting = raw;
+    waiting.c_cc[VMIN] = 0;
+    waiting.c_cc[VTIME] = 1;
+    tcsetattr(fd, TCSANOW, &waiting)?;
+    let byte = read_byte(stdin);
+    tcsetattr(fd, TCSANOW, &raw)?;
+    byte
+}
+
+/// Reads and decodes the next key, returning `None` at end of input
+fn read_key(stdin: &mut impl Read) -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte(stdin)? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        9 => Key::Tab,
+        10 | 13 => Key::Enter,
+        8 | 127 => Key::Backspace,
+        1..=26 => Key::Ctrl((b'a' + byte - 1) as char),
+        27 => match read_byte_soon(stdin)? {
+            None => Key::Escape,
+            Some(b'[') => read_csi(stdin)?,
+            Some(b'O') => match read_byte(stdin)? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                _ => Key::Unknown,
             },
-            // Backspace
-            127 => {
-                if cursor_pos > 0 {
-                    cursor_pos -= 1;
-                    input.remove(cursor_pos);
-                    redraw_line(&input, cursor_pos, prompt)?;
+            Some(next) if (32..=126).contains(&next) => Key::Alt((next as char).to_ascii_lowercase()),
+            Some(_) => Key::Unknown,
+        },
+        32..=126 => Key::Char(byte as char),
+        0xC0..=0xF7 => read_utf8(stdin, byte)?,
+        _ => Key::Unknown,
+    };
+    Ok(Some(key))
+}
+
+/// Decodes a multi-byte UTF-8 character from its lead byte and continuation bytes
+fn read_utf8(stdin: &mut impl Read, lead: u8) -> io::Result<Key> {
+    let len = match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    };
+    let mut bytes = vec![lead];
+    for _ in 1..len {
+        match read_byte(stdin)? {
+            Some(byte) if byte & 0xC0 == 0x80 => bytes.push(byte),
+            _ => return Ok(Key::Unknown),
+        }
+    }
+    Ok(match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+        Some(c) if !c.is_control() => Key::Char(c),
+        _ => Key::Unknown,
+    })
+}
+
+/// Decodes a CSI sequence (`ESC [` already read)
+fn read_csi(stdin: &mut impl Read) -> io::Result<Key> {
+    let mut params = String::new();
+    loop {
+        let Some(byte) = read_byte(stdin)? else {
+            return Ok(Key::Unknown);
+        };
+        if (0x40..=0x7E).contains(&byte) {
+            return Ok(match (byte, params.as_str()) {
+                (b'A', _) => Key::Up,
+                (b'B', _) => Key::Down,
+                (b'C', _) => Key::Right,
+                (b'D', _) => Key::Left,
+                (b'H', _) => Key::Home,
+                (b'F', _) => Key::End,
+                (b'~', "1" | "7") => Key::Home,
+                (b'~', "4" | "8") => Key::End,
+                (b'~', "3") => Key::Delete,
+                _ => Key::Unknown,
+            });
+        }
+        params.push(byte as char);
+    }
+}
+
+/// Text being edited and the cursor's byte offset into it, always on a grapheme boundary
+#[derive(Debug, Default)]
+struct Line {
+    text: String,
+    cursor: usize,
+}
+
+impl Line {
+    fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Start of the grapheme left of the cursor, so `é` or a flag moves as one
+    fn left(&self) -> usize {
+        self.text[..self.cursor].grapheme_indices(true).next_back().map_or(0, |(i, _)| i)
+    }
+
+    /// End of the grapheme right of the cursor
+    fn right(&self) -> usize {
+        self.text[self.cursor..].graphemes(true).next().map_or(self.cursor, |g| self.cursor + g.len())
+    }
+
+    /// Start of the word left of the cursor; words are alphanumeric runs (Alt-B)
+    fn word_left(&self) -> usize {
+        let before = &self.text[..self.cursor];
+        let end = before.trim_end_matches(|c: char| !c.is_alphanumeric()).len();
+        after_last(&before[..end], |c| !c.is_alphanumeric())
+    }
+
+    /// End of the word right of the cursor (Alt-F)
+    fn word_right(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let start = after.len() - after.trim_start_matches(|c: char| !c.is_alphanumeric()).len();
+        let len = after[start..].find(|c: char| !c.is_alphanumeric()).unwrap_or(after.len() - start);
+        self.cursor + start + len
+    }
+
+    /// Start of the whitespace-delimited word left of the cursor (Ctrl-W)
+    fn big_word_left(&self) -> usize {
+        let before = self.text[..self.cursor].trim_end();
+        after_last(before, char::is_whitespace)
+    }
+
+    /// Removes the text between the cursor and `to`, returning it
+    fn kill(&mut self, to: usize) -> String {
+        let (start, end) = if to < self.cursor { (to, self.cursor) } else { (self.cursor, to) };
+        self.cursor = start;
+        self.text.drain(start..end).collect()
+    }
+}
+
+/// How a reverse history search ended
+enum Search {
+    /// Enter: run the match
+    Accept(String),
+    /// Another key: keep the match for editing, then handle the key
+    Edit(String, Key),
+    /// Escape or Ctrl-G: back to the line as it was
+    Abort,
+}
+
+/// Index of the newest entry before `before` containing `query`, skipping `skip`
+fn find_older(history: &[String], query: &str, before: usize, skip: Option<&str>) -> Option<usize> {
+    (0..before.min(history.len()))
+        .rev()
+        .find(|&i| history[i].contains(query) && Some(history[i].as_str()) != skip)
+}
+
+/// Bash-style `(reverse-i-search)` over `history`, cycling older matches with Ctrl-R
+fn reverse_search(stdin: &mut impl Read, out: &mut impl Write, history: &[String], original: &str) -> io::Result<Search> {
+    let mut query = String::new();
+    let mut found: Option<usize> = None;
+    let mut failed = false;
+
+    loop {
+        let text = found.map_or(original, |i| history[i].as_str());
+        let label = if failed { "failed reverse-i-search" } else { "reverse-i-search" };
+        let cursor = found.and_then(|_| text.find(query.as_str())).unwrap_or(0);
+        redraw_line(out, text, cursor, &format!("({})`{}': ", label, query))?;
+
+        let Some(key) = read_key(stdin)? else {
+            return Ok(Search::Abort);
+        };
+        match key {
+            Key::Char(c) => {
+                query.push(c);
+                // The current match stays if it still contains the longer query
+                let from = found.map_or(history.len(), |i| i + 1);
+                match find_older(history, &query, from, None) {
+                    Some(i) => found = Some(i),
+                    None => failed = true,
                 }
-            },
-            // Escape sequence (arrow keys)
-            27 => {
-                let mut seq = [0; 2];
-                if stdin.read(&mut seq)? != 2 {
-                    continue;
+            }
+            Key::Backspace => {
+                query.pop();
+                found = find_older(history, &query, history.len(), None);
+                failed = found.is_none() && !query.is_empty();
+            }
+            Key::Ctrl('r') => {
+                let from = found.unwrap_or(history.len());
+                match find_older(history, &query, from, found.map(|i| history[i].as_str())) {
+                    Some(i) => {
+                        found = Some(i);
+                        failed = false;
+                    }
+                    None => failed = true,
                 }
+            }
+            Key::Enter => return Ok(Search::Accept(text.to_string())),
+            Key::Escape | Key::Ctrl('g') => return Ok(Search::Abort),
+            key => return Ok(Search::Edit(text.to_string(), key)),
+        }
+    }
+}
 
-                if seq[0] == 91 { // '[' after ESC
-                    match seq[1] {
-                        // Up arrow
-                        65 => {
-                            if !history.is_empty() {
-                                if history_index == history.len() {
-                                    // Save current input before history navigation
-                                    current_input_before_history = input.clone();
-                                    history_index = history_index.saturating_sub(1);
-                                    input = history[history_index].clone();
-                                } else if history_index > 0 {
-                                    history_index -= 1;
-                                    input = history[history_index].clone();
-                                }
-                                cursor_pos = input.len();
-                                redraw_line(&input, cursor_pos, prompt)?;
-                            }
-                        },
-                        // Down arrow
-                        66 => {
-                            if !history.is_empty() {
-                                if history_index < history.len().saturating_sub(1) {
-                                    history_index += 1;
-                                    input = history[history_index].clone();
-                                    cursor_pos = input.len();
-                                    redraw_line(&input, cursor_pos, prompt)?;
-                                } else if history_index == history.len().saturating_sub(1) {
-                                    // Restore saved input when returning to bottom
-                                    history_index = history.len();
-                                    input = current_input_before_history.clone();
-                                    cursor_pos = input.len();
-                                    redraw_line(&input, cursor_pos, prompt)?;
-                                }
-                            }
-                        },
-                        // Right arrow
-                        67 => {
-                            if cursor_pos < input.len() {
-                                cursor_pos += 1;
-                                redraw_line(&input, cursor_pos, prompt)?;
-                            }
-                        },
-                        // Left arrow
-                        68 => {
-                            if cursor_pos > 0 {
-                                cursor_pos = cursor_pos.saturating_sub(1);
-                                redraw_line(&input, cursor_pos, prompt)?;
-                            }
-                        },
-                        _ => {}
-                    }
+/// Line editor shared by all prompts, on the terminal in raw mode
+fn edit_line(prompt: &str, history: &[String], completer: Option<&dyn Completer>) -> io::Result<String> {
+    if !interactive() {
+        return read_plain(prompt, true);
+    }
+    let _raw = RawMode::enable()?;
+    let mut stdout = io::stdout();
+    write!(stdout, "{}", prompt)?;
+    stdout.flush()?;
+    edit(&mut io::stdin(), &mut stdout, prompt, history, completer)
+}
+
+/// Edits a line from the key presses in `stdin`, browsing `history` with the
+/// arrow keys and writing to `out` what the terminal shows. Returns
+/// `UnexpectedEof` for Ctrl-D on an empty line or when `stdin` ends.
+pub fn edit(stdin: &mut impl Read, out: &mut impl Write, prompt: &str, history: &[String], completer: Option<&dyn Completer>) -> io::Result<String> {
+    let mut history_index = history.len(); // Start at the end
+    let mut line = Line::default();
+    let mut current_input_before_history = String::new(); // To save input before history navigation
+    let mut killed = String::new(); // Last killed text, for Ctrl-Y
+    let mut last_was_tab = false;
+    // Key that ended a reverse search, to be handled as a normal key
+    let mut pending = None;
+
+    loop {
+        let key = match pending.take() {
+            Some(key) => key,
+            None => match read_key(stdin)? {
+                Some(key) => key,
+                // The terminal is gone; ending the line here would only prompt again
+                None => {
+                    writeln!(out)?;
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of input"));
                 }
             },
-            // Printable ASCII characters (32-126)
-            c if (32..=126).contains(&c) => {
-                input.insert(cursor_pos, c as char);
-                cursor_pos += 1;
-                redraw_line(&input, cursor_pos, prompt)?;
+        };
+        let double_tab = std::mem::replace(&mut last_was_tab, key == Key::Tab);
+
+        match key {
+            // Tab: complete the word, listing candidates on a second press
+            Key::Tab => {
+                if let Some(completer) = completer {
+                    line.cursor = complete(out, &mut line.text, line.cursor, completer, double_tab, prompt)?;
+                }
+            }
+            Key::Enter => {
+                writeln!(out)?;
+                return Ok(line.text);
+            }
+            Key::Char(c) => line.insert(c.encode_utf8(&mut [0; 4])),
+            Key::Backspace if line.cursor > 0 => {
+                line.kill(line.left());
+            }
+            Key::Delete | Key::Ctrl('d') if line.cursor < line.text.len() => {
+                line.kill(line.right());
+            }
+            // Ctrl-D on an empty line is end of input
+            Key::Ctrl('d') if line.text.is_empty() => {
+                writeln!(out)?;
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of input"));
+            }
+            // Ctrl-C abandons the line and starts a fresh one
+            Key::Ctrl('c') => {
+                writeln!(out, "^C")?;
+                write!(out, "{}", prompt)?;
+                line = Line::default();
+                history_index = history.len();
+            }
+            Key::Left | Key::Ctrl('b') => line.cursor = line.left(),
+            Key::Right | Key::Ctrl('f') => line.cursor = line.right(),
+            Key::Home | Key::Ctrl('a') => line.cursor = 0,
+            Key::End | Key::Ctrl('e') => line.cursor = line.text.len(),
+            Key::Alt('b') => line.cursor = line.word_left(),
+            Key::Alt('f') => line.cursor = line.word_right(),
+            Key::Ctrl('w') => killed = line.kill(line.big_word_left()),
+            Key::Alt('d') => killed = line.kill(line.word_right()),
+            Key::Ctrl('u') => killed = line.kill(0),
+            Key::Ctrl('k') => killed = line.kill(line.text.len()),
+            Key::Ctrl('y') => line.insert(&killed),
+            Key::Ctrl('l') => write!(out, "\x1B[H\x1B[2J")?,
+            Key::Ctrl('r') => match reverse_search(stdin, out, history, &line.text)? {
+                Search::Accept(text) => {
+                    line.set(&text);
+                    redraw_line(out, &line.text, line.cursor, prompt)?;
+                    writeln!(out)?;
+                    return Ok(line.text);
+                }
+                Search::Edit(text, key) => {
+                    line.set(&text);
+                    pending = Some(key);
+                }
+                Search::Abort => {}
             },
-            _ => {}
+            Key::Up | Key::Ctrl('p') if !history.is_empty() => {
+                if history_index == history.len() {
+                    // Save current input before history navigation
+                    current_input_before_history = line.text.clone();
+                    history_index = history_index.saturating_sub(1);
+                    line.set(&history[history_index]);
+                } else if history_index > 0 {
+                    history_index -= 1;
+                    line.set(&history[history_index]);
+                }
+            }
+            Key::Down | Key::Ctrl('n') if !history.is_empty() => {
+                if history_index < history.len().saturating_sub(1) {
+                    history_index += 1;
+                    line.set(&history[history_index]);
+                } else if history_index == history.len().saturating_sub(1) {
+                    // Restore saved input when returning to bottom
+                    history_index = history.len();
+                    line.set(&current_input_before_history);
+                }
+            }
+            _ => continue,
         }
+        redraw_line(out, &line.text, line.cursor, prompt)?;
     }
-
-    // Restore terminal settings
-    restore_terminal(&original_term)?;
-    Ok(input)
 }
@@ -0,0 +1,26 @@
+//! Arch Linux installation simulator. The binary drives a real terminal; the
+//! library lets tests run whole lessons through an in-memory frontend.
+
+pub mod cli;
+mod command;
+mod completion;
+pub mod config;
+mod diagnose;
+mod disk;
+mod fdisk;
+pub mod frontend;
+mod hint;
+pub mod history;
+pub mod models;
+pub mod pacing;
+pub mod report;
+pub mod scenario;
+pub mod session;
+pub mod settings;
+mod shell;
+pub mod simulator;
+mod system;
+pub mod theme;
+pub mod utils;
+mod vfs;
+pub mod input_handler;
@@ -1,12 +1,17 @@
-mod config;
-mod models;
-mod simulator;
-mod utils;
-mod input_handler;
-
 use std::path::Path;
-use colored::Colorize;
-use crate::utils::{clear_screen, sleep}; // تم إزالة الواردات غير المستخدمة
+use clap::Parser;
+use archtry::cli::{Cli, Command, RunArgs};
+use archtry::frontend::{Frontend, Terminal};
+use archtry::config::Presets;
+use archtry::models::UserChoices;
+use archtry::scenario::Scenario;
+use archtry::session::Checkpoint;
+use archtry::settings::Settings;
+use archtry::simulator::Options;
+use archtry::theme::{self, Role, Themed};
+use archtry::pacing::{Pacer, Speed};
+use archtry::utils::clear_screen;
+use archtry::{config, history, input_handler, simulator};
 
 const ASCII_LOGO: &str = r#"
      ____  ____  ____  _     _____  ____ ___  _
@@ -17,21 +22,174 @@ const ASCII_LOGO: &str = r#"
 "#;
 
 fn main() {
-    clear_screen();
-    println!("{}", ASCII_LOGO.bright_green());
-    println!("{}", "Loading ArchTry...".bright_blue());
-    sleep(3);
-    
-    // Create log file only if it doesn't exist
-    if !Path::new("/tmp/archtry-log.txt").exists() {
-        let _ = std::fs::File::create("/tmp/archtry-log.txt");
+    let cli = Cli::parse();
+    if cli.no_color {
+        colored::control::set_override(false);
+    }
+    let settings = Settings::load().unwrap_or_else(|err| {
+        eprintln!("{}", format!("Warning: ignoring config file {}", err).themed(Role::Warning));
+        Settings::default()
+    });
+    if !cli.no_color
+        && let Err(problem) = theme::apply(settings.theme, &settings.colors)
+    {
+        eprintln!("{}", format!("Warning: config file colors: {}", problem).themed(Role::Warning));
+    }
+    match cli.command {
+        None => run(cli.run, settings),
+        Some(Command::Run(args)) => run(args, settings),
+        Some(Command::History) => show_history(),
+        Some(Command::Reset) => reset(),
+        Some(Command::Scenarios) => list_scenarios(),
+    }
+}
+
+/// Runs a lesson, asking for whatever the arguments and config file leave open
+fn run(args: RunArgs, settings: Settings) {
+    if let Err(err) = input_handler::install_terminal_guard() {
+        eprintln!("{}", format!("Warning: cannot watch for signals: {}", err).themed(Role::Warning));
+    }
+    if args.no_history || !settings.history.enabled {
+        history::disable();
+    }
+    history::set_max_entries(settings.history.max_entries);
+    if let Some(path) = &args.script
+        && let Err(err) = input_handler::use_script(path)
+    {
+        fail(&format!("{}: {}", path.display(), err));
+    }
+    let scenario = args.scenario.as_deref().map(|name| match Scenario::find(name) {
+        Ok(scenario) => scenario,
+        Err(err) => fail(&format!("{}: {}", name, err)),
+    });
+
+    // Unattended runs default to no waiting and no splash screen
+    let interactive = input_handler::interactive();
+    let speed = args.speed.or(settings.speed).unwrap_or(if interactive { Speed::Normal } else { Speed::Instant });
+    let mut terminal = Terminal::new(Pacer::new(speed));
+
+    if interactive {
+        clear_screen();
+        println!("{}", ASCII_LOGO.themed(Role::Success));
+        println!("{}", "Loading ArchTry...".themed(Role::Header));
+        if let Some(scenario) = &scenario {
+            println!("Scenario: {} - {}", scenario.name.themed(Role::Success), scenario.description);
+        }
+        terminal.pause(3);
     }
     
+    let scenario = scenario.unwrap_or_else(Scenario::bundled);
+    if args.chapter.is_some() && scenario.chapters().next().is_none() {
+        fail("this lesson has no chapters");
+    }
+    if let Some(Some(chapter)) = &args.chapter
+        && !scenario.chapters().any(|stage| &stage.id == chapter)
+    {
+        let ids: Vec<&str> = scenario.chapters().map(|stage| stage.id.as_str()).collect();
+        fail(&format!("unknown chapter '{}' (expected one of: {})", chapter, ids.join(", ")));
+    }
+    // Only interactive runs save progress, so scripts never disturb a learner's session
+    let mut checkpoint = if interactive { Checkpoint::path() } else { None };
+    let resumed = match args.chapter {
+        // A chapter run must not replace a half-finished lesson
+        Some(_) if checkpoint.as_deref().is_some_and(Path::exists) => {
+            println!("{}", "You have a saved session; it is kept, and this chapter run will not be saved.".themed(Role::Warning));
+            checkpoint = None;
+            None
+        }
+        Some(_) => None,
+        None => checkpoint.as_deref().and_then(|path| offer_resume(&mut terminal, path, &scenario)),
+    };
+
     // Get user choices and run simulation
-    let user_choices = config::get_user_choices();
-    simulator::run_simulation(&user_choices);
+    let presets = Presets { gpu: args.gpu, device: args.device, defaults: settings.defaults };
+    let mut user_choices = match &resumed {
+        Some(resumed) => UserChoices { defaults: presets.defaults.clone(), ..UserChoices::new(resumed.gpu, resumed.device) },
+        None => config::get_user_choices(&mut terminal, &presets),
+    };
+    if let Some(desktop) = &args.desktop {
+        user_choices = user_choices.with_answer("desktop", desktop);
+    }
+    let chapter = args.chapter.map(|chapter| {
+        chapter
+            .or_else(|| simulator::choose_chapter(&scenario, &user_choices, &mut terminal))
+            .unwrap_or_else(|| fail("no chapter of this lesson applies to the chosen machine"))
+    });
+    let options = Options {
+        mode: args.mode.or(settings.mode).unwrap_or_default(),
+        hints: args.hints.or(settings.hints).unwrap_or_default(),
+        max_attempts: args.max_attempts.or(settings.max_attempts).unwrap_or(simulator::DEFAULT_MAX_ATTEMPTS),
+        checkpoint,
+        chapter,
+    };
+    let finished = match resumed {
+        Some(resumed) => simulator::resume_scenario(&scenario, resumed, &user_choices, &options, &mut terminal),
+        None => simulator::run_scenario(&scenario, &user_choices, &options, &mut terminal),
+    };
+    // `:quit` at the shell leaves straight away
+    if finished.is_break() {
+        return;
+    }
     
     // Final message
-    println!("\n{}", "Thank you for using ArchTry!".bright_green());
-    println!("{}", "Learn more: https://wiki.archlinux.org/title/Installation_guide".bright_blue());
+    println!("\n{}", "Thank you for using ArchTry!".themed(Role::Success));
+    println!("{}", "Learn more: https://wiki.archlinux.org/title/Installation_guide".themed(Role::Header));
+}
+
+/// Offers to continue a saved session of the same lesson
+fn offer_resume(terminal: &mut Terminal, path: &Path, scenario: &Scenario) -> Option<Checkpoint> {
+    let checkpoint = match Checkpoint::load(path) {
+        Ok(Some(checkpoint)) if checkpoint.scenario == scenario.name => checkpoint,
+        Ok(_) => return None,
+        Err(err) => {
+            eprintln!("{}", format!("Warning: ignoring saved session {}: {}", path.display(), err).themed(Role::Warning));
+            return None;
+        }
+    };
+    let prompt = format!("Resume your previous session? Stopped at: {}", checkpoint.summary(scenario));
+    match terminal.select(&prompt, &["Resume", "Start over"], 0) {
+        0 => Some(checkpoint),
+        _ => None,
+    }
+}
+
+/// Prints the saved history numbered like bash's `history`
+fn show_history() {
+    for (number, command) in history::entries().iter().enumerate() {
+        println!("{:>5}  {}", number + 1, command);
+    }
+}
+
+/// Deletes the saved history and session
+fn reset() {
+    if let Err(err) = history::clear() {
+        fail(&format!("cannot clear history: {}", err));
+    }
+    if let Some(path) = Checkpoint::path()
+        && let Err(err) = Checkpoint::remove(&path)
+    {
+        fail(&format!("cannot remove the saved session {}: {}", path.display(), err));
+    }
+    println!("History and saved session cleared.");
+}
+
+/// Lists the bundled lesson and the installed ones with their descriptions
+fn list_scenarios() {
+    let bundled = Scenario::bundled();
+    println!("{:<16} {} {}", bundled.name.themed(Role::Success), bundled.description, "(bundled)".themed(Role::Muted));
+    for path in Scenario::installed() {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        match Scenario::load(&path) {
+            Ok(scenario) => println!("{:<16} {}", name.themed(Role::Success), scenario.description),
+            Err(err) => println!("{:<16} {}", name.themed(Role::Success), format!("invalid: {}", err).themed(Role::Error)),
+        }
+    }
+    if let Some(dir) = Scenario::dir() {
+        println!("\nInstall lessons by copying TOML files into {}", dir.display());
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", format!("Error: {}", message).themed(Role::Error));
+    std::process::exit(1);
 }
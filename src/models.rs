@@ -1,27 +1,52 @@
+use std::collections::HashMap;
 use std::fmt;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 
 /// Represents user choices for GPU and device type
 #[derive(Debug)]
 pub struct UserChoices {
     pub gpu_type: GpuType,
     pub device_type: DeviceType,
+    /// Answers to lesson dialogs given up front, by variable name
+    pub answers: HashMap<String, String>,
+    /// Answers preselected in lesson dialogs, by variable name
+    pub defaults: HashMap<String, String>,
 }
 
 impl UserChoices {
     /// Creates new UserChoices instance
     pub fn new(gpu_type: GpuType, device_type: DeviceType) -> Self {
-        UserChoices { gpu_type, device_type }
+        UserChoices { gpu_type, device_type, answers: HashMap::new(), defaults: HashMap::new() }
+    }
+
+    /// Answers the lesson's `var` dialog with `value` instead of asking
+    pub fn with_answer(mut self, var: &str, value: &str) -> Self {
+        self.answers.insert(var.to_string(), value.to_string());
+        self
     }
 }
 
 /// Supported GPU types
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum GpuType {
     Amd,
     Intel,
     Nvidia,
 }
 
+impl GpuType {
+    /// Identifier used in scenario files
+    pub fn id(&self) -> &'static str {
+        match self {
+            GpuType::Amd => "amd",
+            GpuType::Intel => "intel",
+            GpuType::Nvidia => "nvidia",
+        }
+    }
+}
+
 impl fmt::Display for GpuType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -33,12 +58,23 @@ impl fmt::Display for GpuType {
 }
 
 /// Supported device types
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceType {
     Laptop,
     Pc,
 }
 
+impl DeviceType {
+    /// Identifier used in scenario files
+    pub fn id(&self) -> &'static str {
+        match self {
+            DeviceType::Laptop => "laptop",
+            DeviceType::Pc => "pc",
+        }
+    }
+}
+
 impl fmt::Display for DeviceType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -47,3 +83,30 @@ impl fmt::Display for DeviceType {
         }
     }
 }
+
+/// How much help a lesson gives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Every step shows its hint, as set by the hint level
+    #[default]
+    Guided,
+    /// Hints stay hidden until asked for with `:hint` or after every 2
+    /// wrong attempts, then reveal the program, its flags, and the whole command
+    Practice,
+    /// No hints, a running clock, and only the score at the end
+    Exam,
+}
+
+/// How much a command step gives away in guided mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HintLevel {
+    /// The whole command, plus the step's guide
+    #[default]
+    Full,
+    /// Only the program to run, plus the step's guide
+    Partial,
+    /// Just the description of the step
+    None,
+}
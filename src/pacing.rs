@@ -0,0 +1,73 @@
+use std::time::Duration;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// How long pauses and progress bars take. Lessons write their pauses in
+/// seconds at `Normal` speed; the other presets scale them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Speed {
+    /// No waiting at all, for scripted runs and tests
+    Instant,
+    /// A quarter of the normal pauses
+    Fast,
+    /// The pauses as the lesson writes them
+    #[default]
+    Normal,
+    /// Twice the normal pauses, closer to how long a real install feels
+    Realistic,
+}
+
+impl Speed {
+    /// Real time for a pause of `secs` at normal speed
+    pub fn scale(self, secs: u64) -> Duration {
+        let normal = Duration::from_secs(secs);
+        match self {
+            Speed::Instant => Duration::ZERO,
+            Speed::Fast => normal / 4,
+            Speed::Normal => normal,
+            Speed::Realistic => normal * 2,
+        }
+    }
+}
+
+/// Source of waiting, replaceable so tests never sleep
+pub trait Clock {
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Really waits
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&mut self, duration: Duration) {
+        if !duration.is_zero() {
+            std::thread::sleep(duration);
+        }
+    }
+}
+
+/// Every wait in the program goes through here, scaled by the chosen speed
+pub struct Pacer {
+    speed: Speed,
+    clock: Box<dyn Clock>,
+}
+
+impl Pacer {
+    pub fn new(speed: Speed) -> Self {
+        Pacer::with_clock(speed, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(speed: Speed, clock: Box<dyn Clock>) -> Self {
+        Pacer { speed, clock }
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Waits for a pause written as `secs` at normal speed
+    pub fn pause(&mut self, secs: u64) {
+        self.clock.sleep(self.speed.scale(secs));
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::frontend::Frontend;
+use crate::theme::{Role, Themed};
+
+/// Points a command step is worth when typed right the first time
+const STEP_POINTS: u32 = 10;
+/// Cost of each wrong attempt
+const MISTAKE_COST: u32 = 2;
+/// Cost of each hint level revealed
+const HINT_COST: u32 = 1;
+
+/// How one command step went
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub description: String,
+    /// Arch Wiki section covering the step's stage
+    pub wiki: Option<String>,
+    /// Command lines tried, the accepted one included
+    pub attempts: u32,
+    /// From the first prompt to the accepted command
+    pub time: Duration,
+    /// Hint levels revealed beyond what the mode shows up front, whether
+    /// asked for or given after mistakes
+    pub hints: u32,
+    pub skipped: bool,
+    /// Help chosen the last time the attempts ran out, if they did
+    pub rescue: Option<Rescue>,
+}
+
+/// Ways out offered once a step's attempts run out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rescue {
+    /// The whole command was shown
+    Answer,
+    /// What was wrong with the last attempt was explained
+    Explanation,
+    /// The step was done for the learner
+    Skip,
+}
+
+impl StepRecord {
+    pub fn new(description: &str, wiki: Option<&str>) -> Self {
+        StepRecord { description: description.to_string(), wiki: wiki.map(str::to_string), ..StepRecord::default() }
+    }
+
+    /// Attempts that were not accepted
+    pub fn mistakes(&self) -> u32 {
+        if self.skipped { self.attempts } else { self.attempts.saturating_sub(1) }
+    }
+
+    /// Skipped steps earn nothing; a completed step always earns a point
+    pub fn points(&self) -> u32 {
+        if self.skipped {
+            return 0;
+        }
+        STEP_POINTS.saturating_sub(self.mistakes() * MISTAKE_COST + self.hints * HINT_COST).max(1)
+    }
+
+    /// Worth reviewing: skipped, or took more than one try
+    pub fn struggled(&self) -> bool {
+        self.skipped || self.mistakes() > 0
+    }
+}
+
+/// Every command step of a run, in order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub steps: Vec<StepRecord>,
+    /// Whole run, pauses and dialogs included
+    pub time: Duration,
+}
+
+impl Report {
+    /// Percentage of the available points earned, 100 for a run without command steps
+    pub fn score(&self) -> u32 {
+        let possible = self.steps.len() as u32 * STEP_POINTS;
+        if possible == 0 {
+            return 100;
+        }
+        let earned: u32 = self.steps.iter().map(StepRecord::points).sum();
+        (earned * 100 + possible / 2) / possible
+    }
+
+    pub fn grade(&self) -> char {
+        match self.score() {
+            90.. => 'A',
+            80..90 => 'B',
+            70..80 => 'C',
+            60..70 => 'D',
+            _ => 'F',
+        }
+    }
+
+    /// Prints the score, and with `details` the totals and the steps worth
+    /// another look
+    pub fn print(&self, frontend: &mut dyn Frontend, details: bool) {
+        let skipped = self.steps.iter().filter(|step| step.skipped).count();
+        let mistakes: u32 = self.steps.iter().map(StepRecord::mistakes).sum();
+        let hints: u32 = self.steps.iter().map(|step| step.hints).sum();
+
+        frontend.println(&format!("\n{}", "Report card".themed(Role::Header)));
+        frontend.println(&format!("Score: {}/100 ({})", self.score(), self.grade()).themed(Role::Success).to_string());
+        frontend.println(&format!("Time: {}", format_duration(self.time)));
+        if !details {
+            return;
+        }
+        frontend.println(&format!("Steps: {} completed, {} skipped", self.steps.len() - skipped, skipped));
+        frontend.println(&format!("Wrong attempts: {}", mistakes));
+        frontend.println(&format!("Hints used: {}", hints));
+
+        let struggled: Vec<&StepRecord> = self.steps.iter().filter(|step| step.struggled()).collect();
+        if struggled.is_empty() {
+            return;
+        }
+        frontend.println(&format!("\n{}", "Steps to review:".themed(Role::Warning)));
+        for step in struggled {
+            let outcome = match step.rescue {
+                _ if step.skipped && step.attempts > 0 => format!("skipped after {} attempts", step.attempts),
+                _ if step.skipped => "skipped".to_string(),
+                Some(Rescue::Answer) => format!("{} attempts, answer shown", step.attempts),
+                Some(Rescue::Explanation) => format!("{} attempts, explained", step.attempts),
+                _ => format!("{} attempts", step.attempts),
+            };
+            frontend.println(&format!("  {} ({}, {})", step.description, outcome, format_duration(step.time)));
+            if let Some(wiki) = &step.wiki {
+                frontend.println(&format!("    {}", wiki.themed(Role::Header)));
+            }
+        }
+    }
+}
+
+/// `1h 02m 03s`, `2m 03s` or `3s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
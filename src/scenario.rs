@@ -0,0 +1,408 @@
+use clap::ValueEnum;
+use crate::command::MatchRules;
+use crate::models::{DeviceType, GpuType};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Lesson bundled into the binary, used when no scenario file is given
+const BUNDLED_SCENARIO: &str = include_str!("../scenarios/default.toml");
+
+/// A lesson: ordered stages of steps, loaded from TOML
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Constant template variables
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Variables whose value depends on another variable
+    #[serde(default)]
+    pub derived: BTreeMap<String, DerivedVar>,
+    pub stages: Vec<Stage>,
+}
+
+/// Variable mapped from another variable, e.g. `gpu` -> microcode package
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DerivedVar {
+    pub from: String,
+    pub values: BTreeMap<String, String>,
+}
+
+/// Group of steps shown under one header
+#[derive(Debug, Deserialize)]
+pub struct Stage {
+    pub id: String,
+    pub header: Option<String>,
+    pub warning: Option<String>,
+    pub success: Option<String>,
+    /// Arch Wiki section on the stage, linked from the report card
+    pub wiki: Option<String>,
+    /// Title in the chapter menu; only stages with one can be started at
+    pub chapter: Option<String>,
+    #[serde(flatten)]
+    pub condition: Condition,
+    pub steps: Vec<Step>,
+}
+
+/// `when` requires every listed variable to match, `unless` rejects any match
+#[derive(Debug, Default, Deserialize)]
+pub struct Condition {
+    #[serde(default)]
+    pub when: BTreeMap<String, String>,
+    #[serde(default)]
+    pub unless: BTreeMap<String, String>,
+}
+
+impl Condition {
+    /// Checks the condition against current variables
+    pub fn holds(&self, vars: &HashMap<String, String>) -> bool {
+        let matches = |(key, value): (&String, &String)| vars.get(key) == Some(value);
+        self.when.iter().all(matches) && !self.unless.iter().any(matches)
+    }
+}
+
+/// Single lesson step
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Step {
+    Command(CommandStep),
+    Input(InputStep),
+    Select(SelectStep),
+    Password(PasswordStep),
+    Message(MessageStep),
+    Pause(PauseStep),
+}
+
+impl Step {
+    /// Returns the step's condition
+    pub fn condition(&self) -> &Condition {
+        match self {
+            Step::Command(step) => &step.condition,
+            Step::Input(step) => &step.condition,
+            Step::Select(step) => &step.condition,
+            Step::Password(step) => &step.condition,
+            Step::Message(step) => &step.condition,
+            Step::Pause(step) => &step.condition,
+        }
+    }
+}
+
+/// Command the user has to type
+#[derive(Debug, Deserialize)]
+pub struct CommandStep {
+    pub command: String,
+    pub description: String,
+    #[serde(default)]
+    pub chroot: bool,
+    pub output: Option<String>,
+    pub progress: Option<Progress>,
+    /// Extra instructions printed under the hint
+    pub guide: Option<String>,
+    /// State the command must leave behind for the step to count
+    pub check: Option<Check>,
+    /// How strictly the typed command has to match `command`
+    #[serde(default)]
+    pub rules: MatchRules,
+    /// Lines an interactive program such as fdisk reads when the step is
+    /// fast-forwarded to reach a later chapter
+    #[serde(default)]
+    pub answers: Vec<String>,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Requirements on the simulated machine, keyed by device path
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Check {
+    /// Partition table per disk: `gpt` or `dos`
+    #[serde(default)]
+    pub label: BTreeMap<String, String>,
+    /// fdisk type name per partition, e.g. `EFI System`
+    #[serde(default)]
+    pub partition_type: BTreeMap<String, String>,
+    /// Minimum size per partition, e.g. `300M`
+    #[serde(default)]
+    pub min_size: BTreeMap<String, String>,
+}
+
+/// Progress bar shown after a command succeeds
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Progress {
+    pub ticks: u64,
+    pub message: String,
+    pub done: String,
+}
+
+/// Free-text answer stored in `var`
+#[derive(Debug, Deserialize)]
+pub struct InputStep {
+    pub var: String,
+    pub prompt: String,
+    pub default: Option<String>,
+    /// Answer used when the step is fast-forwarded and there is no default
+    pub example: Option<String>,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Menu answer stored in `var` (and its label in `<var>_label`)
+#[derive(Debug, Deserialize)]
+pub struct SelectStep {
+    pub var: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Values stored instead of the labels, same order as `options`
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// Variable choosing the option list from `keyed_options`
+    pub keyed_by: Option<String>,
+    #[serde(default)]
+    pub keyed_options: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub default: usize,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+impl SelectStep {
+    /// Returns the option labels for the current variables
+    pub fn labels(&self, vars: &HashMap<String, String>) -> Vec<String> {
+        match &self.keyed_by {
+            Some(key) => vars
+                .get(key)
+                .and_then(|value| self.keyed_options.get(value))
+                .cloned()
+                .unwrap_or_default(),
+            None => self.options.clone(),
+        }
+    }
+
+    /// Returns the value stored for the option at `index`
+    pub fn value(&self, labels: &[String], index: usize) -> String {
+        self.values.get(index).unwrap_or(&labels[index]).clone()
+    }
+}
+
+/// `passwd` prompt for a user
+#[derive(Debug, Deserialize)]
+pub struct PasswordStep {
+    pub user: String,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Text printed without user interaction
+#[derive(Debug, Deserialize)]
+pub struct MessageStep {
+    pub text: String,
+    #[serde(default)]
+    pub style: MessageStyle,
+    #[serde(default)]
+    pub pause: u64,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// How a message step is rendered
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStyle {
+    Header,
+    Success,
+    Warning,
+    Info,
+    Notice,
+    Link,
+    #[default]
+    Text,
+}
+
+/// Delay between steps
+#[derive(Debug, Deserialize)]
+pub struct PauseStep {
+    pub secs: u64,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Errors raised while loading a scenario
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "cannot read scenario: {}", err),
+            ScenarioError::Parse(err) => write!(f, "cannot parse scenario: {}", err),
+            ScenarioError::Invalid(msg) => write!(f, "invalid scenario: {}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for ScenarioError {
+    fn from(err: io::Error) -> Self {
+        ScenarioError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ScenarioError {
+    fn from(err: toml::de::Error) -> Self {
+        ScenarioError::Parse(err)
+    }
+}
+
+impl Scenario {
+    /// Returns the lesson shipped with ArchTry
+    pub fn bundled() -> Scenario {
+        Scenario::parse(BUNDLED_SCENARIO).expect("bundled scenario is valid")
+    }
+
+    /// Directory of installed lessons: `$XDG_DATA_HOME/archtry/scenarios`,
+    /// usually `~/.local/share/archtry/scenarios`
+    pub fn dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("archtry").join("scenarios"))
+    }
+
+    /// Installed lesson files, sorted by name
+    pub fn installed() -> Vec<PathBuf> {
+        let Some(entries) = Scenario::dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Loads a lesson from a file path, or else by the name of an installed
+    /// lesson (its file name without `.toml`) or of the bundled one
+    pub fn find(name: &str) -> Result<Scenario, ScenarioError> {
+        let path = Path::new(name);
+        if path.exists() {
+            return Scenario::load(path);
+        }
+        let installed = Scenario::installed().into_iter().find(|path| path.file_stem().is_some_and(|stem| stem == name));
+        match installed {
+            Some(path) => Scenario::load(&path),
+            None if name == Scenario::bundled().name => Ok(Scenario::bundled()),
+            None => Err(ScenarioError::Io(io::Error::new(io::ErrorKind::NotFound, "no such file or installed lesson"))),
+        }
+    }
+
+    /// Stages that can be started at, in lesson order
+    pub fn chapters(&self) -> impl Iterator<Item = &Stage> {
+        self.stages.iter().filter(|stage| stage.chapter.is_some())
+    }
+
+    /// Loads a scenario from a TOML file
+    pub fn load(path: &Path) -> Result<Scenario, ScenarioError> {
+        Scenario::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses and validates scenario TOML
+    pub fn parse(source: &str) -> Result<Scenario, ScenarioError> {
+        let scenario: Scenario = toml::from_str(source)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Rejects scenarios the engine cannot run
+    fn validate(&self) -> Result<(), ScenarioError> {
+        let invalid = |msg: String| Err(ScenarioError::Invalid(msg));
+        let mut ids = HashSet::new();
+        // Values each select earlier in the lesson can store
+        let mut selected: HashMap<&str, BTreeSet<String>> = HashMap::new();
+
+        for stage in &self.stages {
+            if !ids.insert(stage.id.as_str()) {
+                return invalid(format!("duplicate stage id '{}'", stage.id));
+            }
+            for step in &stage.steps {
+                let Step::Select(select) = step else { continue };
+                let lists: Vec<&Vec<String>> = match &select.keyed_by {
+                    Some(_) => select.keyed_options.values().collect(),
+                    None => vec![&select.options],
+                };
+                if lists.is_empty() || lists.iter().any(|list| list.is_empty()) {
+                    return invalid(format!("select '{}' in stage '{}' has no options", select.var, stage.id));
+                }
+                if lists.iter().any(|list| select.default >= list.len()) {
+                    return invalid(format!("select '{}' default is out of range", select.var));
+                }
+                if !select.values.is_empty() && lists.iter().any(|list| list.len() != select.values.len()) {
+                    return invalid(format!("select '{}' needs one value per option", select.var));
+                }
+                if let Some(key) = &select.keyed_by {
+                    let Some(keys) = self.possible_values(key, &selected) else {
+                        return invalid(format!("select '{}' is keyed by '{}', which no earlier select, derived or constant variable sets", select.var, key));
+                    };
+                    if let Some(missing) = keys.iter().find(|value| !select.keyed_options.contains_key(*value)) {
+                        return invalid(format!("select '{}' has no options for {} '{}'", select.var, key, missing));
+                    }
+                }
+                let stored: Vec<&String> = if select.values.is_empty() { lists.into_iter().flatten().collect() } else { select.values.iter().collect() };
+                selected.entry(select.var.as_str()).or_default().extend(stored.into_iter().cloned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Every value `var` can have, if the lesson limits it: from the earlier
+    /// selects storing it, a derived or constant variable, or the GPU and
+    /// device choices. Free-text inputs have no such limit.
+    fn possible_values(&self, var: &str, selected: &HashMap<&str, BTreeSet<String>>) -> Option<BTreeSet<String>> {
+        if let Some(values) = selected.get(var) {
+            return Some(values.clone());
+        }
+        if let Some(derived) = self.derived.get(var) {
+            return Some(derived.values.values().cloned().collect());
+        }
+        if let Some(value) = self.vars.get(var) {
+            return Some(BTreeSet::from([value.clone()]));
+        }
+        match var {
+            "gpu" => Some(GpuType::value_variants().iter().map(|gpu| gpu.id().to_string()).collect()),
+            "device" => Some(DeviceType::value_variants().iter().map(|device| device.id().to_string()).collect()),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces `{name}` placeholders with variable values, leaving unknown ones intact
+pub fn render(template: &str, vars: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start + 1..];
+        match tail.find('}') {
+            Some(end) if vars.contains_key(&tail[..end]) => {
+                result.push_str(&vars[&tail[..end]]);
+                rest = &tail[end + 1..];
+            }
+            _ => {
+                result.push('{');
+                rest = tail;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::history;
+use crate::models::{DeviceType, GpuType};
+use crate::report::Report;
+use crate::scenario::Scenario;
+use crate::system::System;
+
+/// Where a lesson was left off, saved after every step so that a long run
+/// can be finished in a later session
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Name of the lesson, which has to match to resume
+    pub scenario: String,
+    pub gpu: GpuType,
+    pub device: DeviceType,
+    /// Id of the stage to continue in, and the index of its next step
+    pub stage: String,
+    pub step: usize,
+    /// Template variables, including answers such as the hostname, time zone
+    /// and user name
+    pub vars: BTreeMap<String, String>,
+    /// How the steps so far went, and the time spent on them
+    pub report: Report,
+    pub(crate) system: System,
+}
+
+impl Checkpoint {
+    /// `$XDG_STATE_HOME/archtry/session.toml`, usually `~/.local/state/archtry/session.toml`
+    pub fn path() -> Option<PathBuf> {
+        dirs::state_dir().map(|dir| dir.join("archtry").join("session.toml"))
+    }
+
+    /// Reads the saved session; no file means there is nothing to resume
+    pub fn load(path: &Path) -> io::Result<Option<Checkpoint>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        toml::from_str(&text).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Replaces the saved session in one step, so an interrupted write never
+    /// leaves half a file behind
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if let Some(dir) = path.parent() {
+            history::create_dir(dir)?;
+        }
+        let partial = path.with_extension("toml.partial");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+            .open(&partial)?;
+        file.write_all(text.as_bytes())?;
+        fs::rename(&partial, path)
+    }
+
+    /// Forgets the saved session, if there is one
+    pub fn remove(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Where the session stopped, e.g. `Configuring timezone (12 commands done)`
+    pub fn summary(&self, scenario: &Scenario) -> String {
+        let stage = scenario
+            .stages
+            .iter()
+            .find(|stage| stage.id == self.stage)
+            .and_then(|stage| stage.header.as_deref())
+            .map_or(self.stage.as_str(), |header| header.trim_end_matches('.'));
+        match self.report.steps.len() {
+            1 => format!("{} (1 command done)", stage),
+            done => format!("{} ({} commands done)", stage, done),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use serde::Deserialize;
+use crate::history::DEFAULT_MAX_ENTRIES;
+use crate::models::{HintLevel, Mode};
+use crate::pacing::Speed;
+use crate::theme::Theme;
+
+/// Personal preferences from `~/.config/archtry/config.toml`. Command-line
+/// flags take precedence over everything here.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Pacing when `--speed` is not given
+    pub speed: Option<Speed>,
+    pub theme: Theme,
+    /// Per-role colours over the theme, e.g. `header = "magenta"`
+    pub colors: BTreeMap<String, String>,
+    /// Mode when `--mode` is not given
+    pub mode: Option<Mode>,
+    /// Hint level when `--hints` is not given
+    pub hints: Option<HintLevel>,
+    /// Wrong attempts at a step before help is offered when
+    /// `--max-attempts` is not given; 0 never offers it
+    pub max_attempts: Option<u32>,
+    /// Answers preselected in dialogs, by variable: `gpu`, `device`, or a
+    /// lesson's own such as `hostname`, `region`, `city` and `desktop`
+    pub defaults: HashMap<String, String>,
+    pub history: HistorySettings,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistorySettings {
+    /// Save typed commands between sessions
+    pub enabled: bool,
+    pub max_entries: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        HistorySettings { enabled: true, max_entries: DEFAULT_MAX_ENTRIES }
+    }
+}
+
+/// Why the configuration file could not be used
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            SettingsError::Parse(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl Settings {
+    /// `$XDG_CONFIG_HOME/archtry/config.toml`, usually `~/.config/archtry/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("archtry").join("config.toml"))
+    }
+
+    /// Reads the configuration file; a missing file means all defaults
+    pub fn load() -> Result<Settings, SettingsError> {
+        let Some(path) = Settings::path() else {
+            return Ok(Settings::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(source) => Settings::parse(&source).map_err(|err| SettingsError::Parse(path, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(err) => Err(SettingsError::Io(path, err)),
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Settings, toml::de::Error> {
+        toml::from_str(source)
+    }
+}
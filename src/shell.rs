@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use crate::command::{CommandLine, Redirect};
+
+/// Looks up a shell variable; `?` is the last exit status
+pub type Lookup<'a> = dyn Fn(&str) -> Option<String> + 'a;
+
+/// Piece of a word as typed
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Var(String),
+}
+
+/// Word as typed, expanded when its command runs.
+/// A leading unquoted `NAME=` is split off into `assign`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Word {
+    assign: Option<String>,
+    parts: Vec<Part>,
+}
+
+impl Word {
+    fn push(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(Part::Text(text)) => text.push(c),
+            _ => self.parts.push(Part::Text(c.to_string())),
+        }
+    }
+
+    /// Expands the value, without any `NAME=` prefix
+    fn value(&self, var: &Lookup) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Var(name) => var(name).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Expands the whole word, as an argument
+    fn expand(&self, var: &Lookup) -> String {
+        match &self.assign {
+            Some(name) => format!("{}={}", name, self.value(var)),
+            None => self.value(var),
+        }
+    }
+}
+
+/// Control operator between pipelines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    /// First pipeline, or after `;`
+    Always,
+    /// After `&&`: runs if the previous pipeline succeeded
+    And,
+    /// After `||`: runs if the previous pipeline failed
+    Or,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(Word),
+    Redirect { append: bool },
+    Pipe,
+    Control(Connector),
+}
+
+impl Token {
+    fn text(&self) -> &'static str {
+        match self {
+            Token::Word(_) => "word",
+            Token::Redirect { append: true } => ">>",
+            Token::Redirect { append: false } => ">",
+            Token::Pipe => "|",
+            Token::Control(Connector::And) => "&&",
+            Token::Control(Connector::Or) => "||",
+            Token::Control(Connector::Always) => ";",
+        }
+    }
+}
+
+/// Simple command before expansion, with the text it was typed as
+#[derive(Debug, Default)]
+struct Simple {
+    assignments: Vec<(String, Word)>,
+    words: Vec<Word>,
+    redirect: Option<(bool, Word)>,
+    text: String,
+}
+
+impl Simple {
+    fn is_empty(&self) -> bool {
+        self.assignments.is_empty() && self.words.is_empty() && self.redirect.is_none()
+    }
+
+    fn expand(&self, var: &Lookup) -> CommandLine {
+        CommandLine {
+            assignments: self.assignments.iter().map(|(name, value)| (name.clone(), value.value(var))).collect(),
+            words: self.words.iter().map(|word| word.expand(var)).collect(),
+            redirect: self.redirect.as_ref().map(|(append, path)| Redirect { append: *append, path: path.expand(var) }),
+            text: self.text.clone(),
+        }
+    }
+}
+
+/// Commands joined by `|`
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    commands: Vec<Simple>,
+}
+
+impl Pipeline {
+    /// Expands every command against the current variables
+    pub fn expand(&self, var: &Lookup) -> Vec<CommandLine> {
+        self.commands.iter().map(|command| command.expand(var)).collect()
+    }
+}
+
+/// A parsed command line: pipelines joined by `;`, `&&` and `||`
+#[derive(Debug, Default)]
+pub struct Script {
+    pipelines: Vec<(Connector, Pipeline)>,
+}
+
+impl Script {
+    pub fn pipelines(&self) -> impl Iterator<Item = (Connector, &Pipeline)> {
+        self.pipelines.iter().map(|(connector, pipeline)| (*connector, pipeline))
+    }
+
+    /// Every simple command in the script, expanded with the variables known now
+    /// and with `| tee` folded into a redirection. Assignments made earlier on
+    /// the line are taken into account.
+    pub fn commands(&self, var: &Lookup) -> Vec<CommandLine> {
+        let mut assigned: HashMap<String, String> = HashMap::new();
+        let mut commands = Vec::new();
+        for (_, pipeline) in &self.pipelines {
+            let expanded = pipeline.expand(&|name| assigned.get(name).cloned().or_else(|| var(name)));
+            for command in &expanded {
+                let exported = match command.program() {
+                    Some("export") => command.args().iter().filter_map(|a| a.split_once('=')).map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                    Some(_) => Vec::new(),
+                    None => command.assignments.clone(),
+                };
+                assigned.extend(exported);
+            }
+            commands.extend(fold_tee(expanded));
+        }
+        commands
+    }
+}
+
+/// Rewrites `cmd | tee [-a] FILE` as `cmd > FILE` (or `>>`), the form lessons expect
+pub fn fold_tee(mut commands: Vec<CommandLine>) -> Vec<CommandLine> {
+    let count = commands.len();
+    if count < 2 {
+        return commands;
+    }
+    let (last, previous) = (&commands[count - 1], &commands[count - 2]);
+    let args = last.args();
+    let append = match args.as_slice() {
+        [_] => false,
+        ["-a" | "--append", _] => true,
+        _ => return commands,
+    };
+    if last.program() != Some("tee") || last.redirect.is_some() || previous.redirect.is_some() {
+        return commands;
+    }
+    let path = args[args.len() - 1].to_string();
+    let text = format!("{} | {}", previous.text, last.text);
+    commands.pop();
+    let folded = commands.last_mut().expect("pipeline has two commands");
+    folded.redirect = Some(Redirect { append, path });
+    folded.text = text;
+    commands
+}
+
+/// Parses a command line, reporting bash's syntax errors
+pub fn parse(line: &str) -> Result<Script, String> {
+    let unexpected = |token: &str| format!("bash: syntax error near unexpected token `{}'", token);
+    let mut script = Script::default();
+    let mut pipeline = Pipeline::default();
+    let mut simple = Simple::default();
+    let mut connector = Connector::Always;
+    // Set after `|`, `&&` or `||`, which need a command to follow
+    let mut pending = false;
+
+    let mut tokens = tokenize(line)?.into_iter();
+    while let Some((token, text)) = tokens.next() {
+        match token {
+            Token::Word(word) => {
+                pending = false;
+                append_text(&mut simple.text, &text);
+                match word.assign.clone() {
+                    Some(name) if simple.words.is_empty() => simple.assignments.push((name, word)),
+                    _ => simple.words.push(word),
+                }
+            }
+            Token::Redirect { append } => {
+                let operator = Token::Redirect { append }.text();
+                match tokens.next() {
+                    Some((Token::Word(path), path_text)) => {
+                        append_text(&mut simple.text, &format!("{} {}", operator, path_text));
+                        simple.redirect = Some((append, path));
+                        pending = false;
+                    }
+                    Some((other, _)) => return Err(unexpected(other.text())),
+                    None => return Err(unexpected("newline")),
+                }
+            }
+            Token::Pipe => {
+                if simple.is_empty() {
+                    return Err(unexpected("|"));
+                }
+                pipeline.commands.push(std::mem::take(&mut simple));
+                pending = true;
+            }
+            Token::Control(next) => {
+                if simple.is_empty() {
+                    return Err(unexpected(Token::Control(next).text()));
+                }
+                pipeline.commands.push(std::mem::take(&mut simple));
+                script.pipelines.push((connector, std::mem::take(&mut pipeline)));
+                connector = next;
+                pending = next != Connector::Always;
+            }
+        }
+    }
+    if pending {
+        return Err("bash: syntax error: unexpected end of file".to_string());
+    }
+    if !simple.is_empty() {
+        pipeline.commands.push(simple);
+        script.pipelines.push((connector, pipeline));
+    }
+    Ok(script)
+}
+
+fn append_text(text: &mut String, word: &str) {
+    if !text.is_empty() {
+        text.push(' ');
+    }
+    text.push_str(word);
+}
+
+/// Splits a line into words and operators, honouring quotes, `$NAME`, `${NAME}`
+/// and `#` comments. Each token comes with the text it was typed as.
+fn tokenize(line: &str) -> Result<Vec<(Token, String)>, String> {
+    let mut tokens = Vec::new();
+    let mut word: Option<Word> = None;
+    // True while the word so far could still be the NAME of `NAME=value`
+    let mut plain = true;
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = line.char_indices().peekable();
+
+    macro_rules! finish_word {
+        ($end:expr) => {
+            if let Some(done) = word.take() {
+                tokens.push((Token::Word(done), line[start..$end].to_string()));
+            }
+        };
+    }
+
+    while let Some((index, c)) = chars.next() {
+        if word.is_none() && quote.is_none() && !c.is_whitespace() && !"|&;>".contains(c) {
+            if c == '#' {
+                break;
+            }
+            word = Some(Word::default());
+            plain = true;
+            start = index;
+        }
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '$') => {
+                let name = if chars.next_if(|(_, c)| *c == '{').is_some() {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' || c == '?' => name.push(c),
+                            _ => return Err(format!("bash: {}: bad substitution", &line[index..])),
+                        }
+                    }
+                    Some(name)
+                } else if chars.next_if(|(_, c)| *c == '?').is_some() {
+                    Some("?".to_string())
+                } else if chars.peek().is_some_and(|(_, c)| c.is_ascii_alphabetic() || *c == '_') {
+                    let mut name = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+                        name.push(c);
+                    }
+                    Some(name)
+                } else {
+                    None
+                };
+                let current = word.get_or_insert_with(Word::default);
+                match name {
+                    Some(name) => current.parts.push(Part::Var(name)),
+                    None => current.push('$'),
+                }
+                plain = false;
+            }
+            (Some('"'), '\\') => {
+                if let Some((_, next)) = chars.next() {
+                    if !matches!(next, '"' | '\\' | '$' | '`') {
+                        word.get_or_insert_with(Word::default).push('\\');
+                    }
+                    word.get_or_insert_with(Word::default).push(next);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(Word::default).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                plain = false;
+            }
+            (None, '\\') => {
+                if let Some((_, next)) = chars.next() {
+                    word.get_or_insert_with(Word::default).push(next);
+                }
+                plain = false;
+            }
+            (None, '=') if plain && word.as_ref().is_some_and(|w| w.assign.is_none() && !w.parts.is_empty()) => {
+                let current = word.as_mut().expect("checked above");
+                let name = current.value(&|_| None);
+                current.assign = Some(name);
+                current.parts.clear();
+                plain = false;
+            }
+            (None, '>' | '|' | '&' | ';') => {
+                finish_word!(index);
+                let (token, text) = match c {
+                    '>' if chars.next_if(|(_, c)| *c == '>').is_some() => (Token::Redirect { append: true }, ">>"),
+                    '>' => (Token::Redirect { append: false }, ">"),
+                    '|' if chars.next_if(|(_, c)| *c == '|').is_some() => (Token::Control(Connector::Or), "||"),
+                    '|' => (Token::Pipe, "|"),
+                    '&' if chars.next_if(|(_, c)| *c == '&').is_some() => (Token::Control(Connector::And), "&&"),
+                    '&' => return Err("bash: background jobs (&) are not available in the simulator".to_string()),
+                    _ => (Token::Control(Connector::Always), ";"),
+                };
+                tokens.push((token, text.to_string()));
+            }
+            (None, c) if c.is_whitespace() => finish_word!(index),
+            (None, c) => {
+                let name_char = c.is_ascii_alphabetic() || c == '_' || (c.is_ascii_digit() && index > start);
+                plain &= name_char;
+                word.get_or_insert_with(Word::default).push(c);
+            }
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("bash: unexpected EOF while looking for matching `{}'", q));
+    }
+    finish_word!(line.len());
+    Ok(tokens)
+}
+
@@ -1,416 +1,852 @@
-use colored::Colorize;
-use dialoguer::{Select, Input};
-use crate::models::{DeviceType, GpuType, UserChoices};
-use crate::utils::{clear_screen, create_progress_bar, show_header, show_success, show_warning, sleep};
-use crate::input_handler::read_input_with_history;
-use std::io::Write;
+use crate::theme::{Role, Themed};
+use crate::command::{self, CommandLine, MatchRules};
+use crate::completion::StepCompleter;
+use crate::diagnose;
+use crate::hint;
+use crate::models::{DeviceType, GpuType, HintLevel, Mode, UserChoices};
+use crate::report::{Report, Rescue, StepRecord};
+use crate::scenario::{render, Check, CommandStep, InputStep, MessageStyle, Progress, Scenario, SelectStep, Stage, Step};
+use crate::session::Checkpoint;
+use crate::shell::{self, Script};
+use crate::system::{Run, System};
+use crate::utils::{show_header, show_success, show_warning};
+use crate::frontend::{Frontend, Memory};
+use std::collections::HashMap;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How a lesson is run, as opposed to what it installs
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub mode: Mode,
+    /// How much guided mode shows up front
+    pub hints: HintLevel,
+    /// Wrong attempts at a step before help is offered; 0 never offers it
+    pub max_attempts: u32,
+    /// Where to save a checkpoint after every step, if anywhere
+    pub checkpoint: Option<PathBuf>,
+    /// Stage id to start at, with the stages before it fast-forwarded
+    pub chapter: Option<String>,
+}
 
-/// Runs full Arch Linux installation simulation
-pub fn run_simulation(user_choices: &UserChoices) {
-    show_header("Starting Arch Linux Installation Simulator...");
-    sleep(3);
-    show_header("Welcome to Arch Linux!");
-    sleep(2);
-
-    // Network setup
-    if let DeviceType::Pc = user_choices.device_type {
-        println!("\n{}", "Connected via LAN.".bright_green());
-        sleep(1);
-    } else if let DeviceType::Laptop = user_choices.device_type {
-        simulate_wifi_setup();
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mode: Mode::default(),
+            hints: HintLevel::default(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            checkpoint: None,
+            chapter: None,
+        }
     }
+}
 
-    // Disk preparation
-    simulate_partitioning();
-    simulate_mount();
+/// Wrong attempts at a step before the answer, an explanation or a skip is
+/// offered
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Wrong attempts in practice mode before the hint reveals a little more;
+/// fixed, as the `--mode` help says, unlike `max_attempts`
+const REVEAL_AFTER: u32 = 2;
+
+/// Lesson commands, typed at the shell prompt (not in dialogs); the leading
+/// colon keeps them apart from anything a real shell would run
+const LESSON_COMMANDS: &[(&str, &str)] = &[
+    (":hint", "reveal a little more of the command"),
+    (":answer", "reveal the whole command"),
+    (":skip", "have the step done for you, for no points"),
+    (":back", "undo the previous step and do it again"),
+    (":menu", "start over at another chapter"),
+    (":save", "save your progress now"),
+    (":quit", "leave the lesson; it can be resumed later"),
+    (":help", "list these commands"),
+];
+
+/// Where a lesson command sends the lesson instead of on to the next step
+enum Jump {
+    Quit,
+    Back,
+    Chapter(String),
+}
 
-    // Base system installation
-    simulate_pacstrap(&user_choices.gpu_type);
+/// State before an interactive step, restored by `:back`
+struct Snapshot {
+    /// Stage and step index
+    at: (usize, usize),
+    vars: HashMap<String, String>,
+    system: System,
+    /// Report records kept
+    steps: usize,
+}
 
-    // Final configuration
-    simulate_final_steps();
+/// Runs full Arch Linux installation simulation
+pub fn run_simulation(user_choices: &UserChoices, frontend: &mut dyn Frontend) -> ControlFlow<()> {
+    run_scenario(&Scenario::bundled(), user_choices, &Options::default(), frontend)
 }
 
-/// Simulates Wi-Fi setup for laptops
-fn simulate_wifi_setup() {
-    show_header("Setting up Wi-Fi...");
-    show_warning("Note: You need to manually connect to Wi-Fi.");
+/// Runs every stage of a scenario whose condition holds, then prints the
+/// report card. Breaks if the user typed `:quit` before the end.
+pub fn run_scenario(scenario: &Scenario, user_choices: &UserChoices, options: &Options, frontend: &mut dyn Frontend) -> ControlFlow<()> {
+    let mut simulation = Simulation::new(scenario, user_choices, options, frontend);
+    let first = match &options.chapter {
+        Some(chapter) => simulation.skip_to(chapter),
+        None => 0,
+    };
+    simulation.run(first, 0)
+}
 
-    simulate_command("ip a", "Show network interfaces", false, || {
-        println!("1: wlan0: <NO-CARRIER,BROADCAST,MULTICAST,UP>");
-    });
+/// Continues a lesson where a checkpoint left it, with its answers, progress
+/// and simulated machine
+pub fn resume_scenario(
+    scenario: &Scenario,
+    checkpoint: Checkpoint,
+    user_choices: &UserChoices,
+    options: &Options,
+    frontend: &mut dyn Frontend,
+) -> ControlFlow<()> {
+    let mut simulation = Simulation::new(scenario, user_choices, options, frontend);
+    simulation.vars = checkpoint.vars.into_iter().collect();
+    simulation.system = checkpoint.system;
+    simulation.time_before = checkpoint.report.time;
+    simulation.report = checkpoint.report;
+    match scenario.stages.iter().position(|stage| stage.id == checkpoint.stage) {
+        Some(stage) => simulation.run(stage, checkpoint.step),
+        None => {
+            show_warning(simulation.frontend, &format!("The lesson has no stage '{}' any more; starting over.", checkpoint.stage));
+            run_scenario(scenario, user_choices, options, simulation.frontend)
+        }
+    }
+}
 
-    simulate_command("iwctl station wlan0 get-networks", "Scan for Wi-Fi", false, || {
-        println!("Available networks: archtry_wifi");
-    });
+/// State of a running lesson: template variables and the simulated machine
+struct Simulation<'a> {
+    scenario: &'a Scenario,
+    vars: HashMap<String, String>,
+    system: System,
+    frontend: &'a mut dyn Frontend,
+    options: &'a Options,
+    /// Dialog answers given on the command line
+    answers: HashMap<String, String>,
+    /// Dialog answers preselected by the config file
+    defaults: HashMap<String, String>,
+    gpu_type: GpuType,
+    device_type: DeviceType,
+    report: Report,
+    /// Time spent in earlier sessions of a resumed lesson
+    time_before: Duration,
+    /// Where to save progress; cleared if saving fails
+    checkpoint: Option<&'a Path>,
+    /// Stage and step index of the step being run
+    at: (usize, usize),
+    /// One per interactive step run this session, the current one last
+    snapshots: Vec<Snapshot>,
+}
 
-    simulate_command("iwctl station wlan0 connect archtry_wifi", "Connect to Wi-Fi", false, || {
-        println!("Connected to archtry_wifi.");
-    });
+impl<'a> Simulation<'a> {
+    /// Seeds variables from the scenario and the user's choices
+    fn new(scenario: &'a Scenario, user_choices: &UserChoices, options: &'a Options, frontend: &'a mut dyn Frontend) -> Self {
+        let mut simulation = Simulation {
+            scenario,
+            vars: HashMap::new(),
+            system: System::new(),
+            frontend,
+            options,
+            answers: user_choices.answers.clone(),
+            defaults: user_choices.defaults.clone(),
+            gpu_type: user_choices.gpu_type,
+            device_type: user_choices.device_type,
+            report: Report::default(),
+            time_before: Duration::ZERO,
+            checkpoint: options.checkpoint.as_deref(),
+            at: (0, 0),
+            snapshots: Vec::new(),
+        };
+        simulation.reset();
+        simulation
+    }
 
-    sleep(1);
-}
+    /// Puts the lesson back at its start on a fresh machine; only the time
+    /// spent is kept
+    fn reset(&mut self) {
+        self.vars = self.scenario.vars.clone().into_iter().collect();
+        self.vars.insert("gpu".to_string(), self.gpu_type.id().to_string());
+        self.vars.insert("device".to_string(), self.device_type.id().to_string());
+        self.resolve_derived();
+        self.system = System::new();
+        self.report.steps.clear();
+        self.snapshots.clear();
+    }
 
-/// Simulates disk partitioning with educational guidance
-fn simulate_partitioning() {
-    show_header("Partitioning disk...");
-    show_warning("Note: You need to manually partition the disk.");
-
-    simulate_command("lsblk", "Show disk layout", false, || {
-        println!("NAME        SIZE\nsdz         50.0G");
-    });
-
-    // محاكاة موسعة لأمر fdisk مع تعليمات مفصلة
-    simulate_command("fdisk /dev/sdz", "Create partitions", false, || {
-        println!("\n{}", "Starting fdisk simulation...".bright_blue());
-        sleep(1);
-        
-        // Step 1: Create GPT partition table
-        println!("\n{}", "Step 1: Create a new GPT partition table".bright_yellow());
-        println!("Command: {}", "g".bright_green());
-        println!("Created a new GPT disklabel (GUID: 12345678-1234-1234-1234-1234567890AB).");
-        sleep(1);
-        
-        // Step 2: Create EFI System partition (1GB)
-        println!("\n{}", "Step 2: Create EFI System partition (1GB)".bright_yellow());
-        println!("Command: {}", "n".bright_green());
-        println!("Partition number (1-128, default 1): ");
-        println!("First sector (2048-104857566, default 2048): ");
-        println!("Last sector, +/-sectors or +/-size{{K,M,G,T,P}} (2048-104857566, default 104857566): {}", "+1G".bright_green());
-        println!("Created a new partition 1 of type 'Linux filesystem' and of size 1 GiB.");
-        sleep(1);
-        
-        // Step 3: Change partition type to EFI System
-        println!("\n{}", "Step 3: Change partition type to EFI System".bright_yellow());
-        println!("Command: {}", "t".bright_green());
-        println!("Partition number (1,2, default 2): {}", "1".bright_green());
-        println!("Partition type or alias (type L to list all): {}", "1".bright_green());
-        println!("Changed type of partition 'Linux filesystem' to 'EFI System'.");
-        sleep(1);
-        
-        // Step 4: Create root partition (using remaining space)
-        println!("\n{}", "Step 4: Create root partition (using remaining space)".bright_yellow());
-        println!("Command: {}", "n".bright_green());
-        println!("Partition number (2-128, default 2): ");
-        println!("First sector (1128448-104857566, default 1128448): ");
-        println!("Last sector, +/-sectors or +/-size{{K,M,G,T,P}} (1128448-104857566, default 104857566): ");
-        println!("Created a new partition 2 of type 'Linux filesystem' and of size 49 GiB.");
-        sleep(1);
-        
-        // Step 5: Write changes to disk
-        println!("\n{}", "Step 5: Write changes to disk".bright_yellow());
-        println!("Command: {}", "w".bright_green());
-        println!("The partition table has been altered.");
-        println!("Calling ioctl() to re-read partition table.");
-        println!("Syncing disks.");
-        
-        sleep(1);
-        println!("\n{}", "Partitioning completed successfully.".bright_green());
-    });
-}
+    /// Runs the stages whose condition holds, starting at step `first_step` of
+    /// stage `first_stage`, then prints the report card and forgets the
+    /// checkpoint
+    fn run(&mut self, first_stage: usize, first_step: usize) -> ControlFlow<()> {
+        match self.options.mode {
+            Mode::Guided => {}
+            Mode::Practice => show_warning(self.frontend, &format!(
+                "Practice mode: hints are hidden. Type :hint to reveal more of the command; every {} wrong attempts reveal more on their own.",
+                REVEAL_AFTER
+            )),
+            Mode::Exam => show_warning(self.frontend, "Exam mode: no hints, and the clock is running."),
+        }
+        self.frontend.println("Type :help at the prompt for lesson commands such as :skip, :back and :quit.");
+        let mut at = (first_stage, first_step);
+        loop {
+            match self.run_from(at) {
+                ControlFlow::Continue(()) => break,
+                ControlFlow::Break(Jump::Quit) => {
+                    if self.checkpoint.is_some() {
+                        show_warning(self.frontend, "\nYour progress is saved. Run archtry again to pick up where you left off.");
+                    }
+                    return ControlFlow::Break(());
+                }
+                ControlFlow::Break(Jump::Back) => at = self.go_back(),
+                ControlFlow::Break(Jump::Chapter(chapter)) => {
+                    self.reset();
+                    at = (self.skip_to(&chapter), 0);
+                }
+            }
+        }
+        self.report.time = self.elapsed();
+        self.report.print(self.frontend, self.options.mode != Mode::Exam);
+        if let Some(path) = self.checkpoint
+            && let Err(err) = Checkpoint::remove(path)
+        {
+            show_warning(self.frontend, &format!("Warning: cannot remove the saved session: {}", err));
+        }
+        ControlFlow::Continue(())
+    }
 
-/// Simulates mounting partitions
-fn simulate_mount() {
-    show_header("Mounting partitions...");
+    /// Runs the stages from step `at.1` of stage `at.0` on, until the end or
+    /// a lesson command leaves the way
+    fn run_from(&mut self, (first_stage, first_step): (usize, usize)) -> ControlFlow<Jump> {
+        let scenario = self.scenario;
+        for (index, stage) in scenario.stages.iter().enumerate().skip(first_stage) {
+            if stage.condition.holds(&self.vars) {
+                let skip = if index == first_stage { first_step } else { 0 };
+                self.run_stage(index, stage, skip)?;
+            }
+        }
+        ControlFlow::Continue(())
+    }
 
-    simulate_command("mount /dev/sdz2 /mnt", "Mount root partition", false, || {
-        println!("Root partition mounted.");
-    });
+    /// Undoes the step in progress and the one before it, and returns where
+    /// to pick up again. `:back` only jumps here with two snapshots taken.
+    fn go_back(&mut self) -> (usize, usize) {
+        self.snapshots.pop();
+        let snapshot = self.snapshots.pop().expect("no step to go back to");
+        self.vars = snapshot.vars;
+        self.system = snapshot.system;
+        self.report.steps.truncate(snapshot.steps);
+        self.save(snapshot.at);
+        snapshot.at
+    }
 
-    simulate_command("mount --mkdir /dev/sdz1 /mnt/boot/efi", "Mount EFI partition", false, || {
-        println!("EFI partition mounted.");
-    });
+    /// Time spent on the lesson, earlier sessions included
+    fn elapsed(&self) -> Duration {
+        self.time_before + self.frontend.elapsed()
+    }
 
-    show_success("Partitions mounted.");
-}
+    /// Saves where the lesson is, so it can resume at step `at.1` of stage
+    /// `at.0`. After a failure it stops trying, so one warning is enough.
+    fn save(&mut self, (stage, step): (usize, usize)) {
+        let Some(path) = self.checkpoint else {
+            return;
+        };
+        self.report.time = self.elapsed();
+        let checkpoint = Checkpoint {
+            scenario: self.scenario.name.clone(),
+            gpu: self.gpu_type,
+            device: self.device_type,
+            stage: self.scenario.stages[stage].id.clone(),
+            step,
+            vars: self.vars.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+            report: self.report.clone(),
+            system: self.system.clone(),
+        };
+        if let Err(err) = checkpoint.save(path) {
+            show_warning(self.frontend, &format!("Warning: cannot save progress to {}: {}", path.display(), err));
+            self.checkpoint = None;
+        }
+    }
 
-/// Simulates base system installation
-fn simulate_pacstrap(gpu_type: &GpuType) {
-    show_header("Installing base system...");
+    /// Recomputes variables derived from other variables
+    fn resolve_derived(&mut self) {
+        for (name, derived) in &self.scenario.derived {
+            let value = self.vars.get(&derived.from).and_then(|from| derived.values.get(from)).cloned();
+            if let Some(value) = value {
+                self.vars.insert(name.clone(), value);
+            }
+        }
+    }
 
-    let base_packages = match gpu_type {
-        GpuType::Amd => "amd-ucode",
-        GpuType::Intel => "intel-ucode",
-        GpuType::Nvidia => "nvidia",
-    };
+    /// Runs stage `index` from step `first_step` on, with its header, warning
+    /// and success banners, saving a checkpoint after every step
+    fn run_stage(&mut self, index: usize, stage: &Stage, first_step: usize) -> ControlFlow<Jump> {
+        if let Some(header) = &stage.header {
+            show_header(self.frontend, &render(header, &self.vars));
+        }
+        if let Some(warning) = &stage.warning {
+            show_warning(self.frontend, &render(warning, &self.vars));
+        }
 
-    simulate_command(
-        &format!("pacstrap -K /mnt base base-devel linux linux-firmware {} grub efibootmgr", base_packages),
-        "Install base system and GRUB",
-        false,
-        || {
-            let pb = create_progress_bar(10);
-            for i in 0..10 {
-                pb.set_position(i);
-                pb.set_message("Installing packages...");
-                sleep(1);
+        for (step_index, step) in stage.steps.iter().enumerate().skip(first_step) {
+            if step.condition().holds(&self.vars) {
+                self.at = (index, step_index);
+                if !matches!(step, Step::Message(_) | Step::Pause(_)) {
+                    self.snapshots.push(Snapshot {
+                        at: self.at,
+                        vars: self.vars.clone(),
+                        system: self.system.clone(),
+                        steps: self.report.steps.len(),
+                    });
+                }
+                self.run_step(step, stage.wiki.as_deref())?;
+                self.resolve_derived();
+                self.save((index, step_index + 1));
             }
-            pb.finish_with_message("Installation complete.");
-        },
-    );
+        }
 
-    show_success("Base system installed.");
-}
+        if let Some(success) = &stage.success {
+            show_success(self.frontend, &render(success, &self.vars));
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Runs a single step, storing any answer in `vars` and how a command
+    /// step went in the report
+    fn run_step(&mut self, step: &Step, wiki: Option<&str>) -> ControlFlow<Jump> {
+        let vars = &mut self.vars;
+        let frontend = &mut *self.frontend;
+        match step {
+            Step::Command(step) => return self.run_command_step(step, wiki),
+            Step::Input(step) => {
+                let prompt = render(&step.prompt, vars);
+                let answer = match self.answers.get(&step.var) {
+                    Some(answer) => {
+                        frontend.println(&format!("{}: {}", prompt, answer));
+                        answer.clone()
+                    }
+                    None => ask(frontend, &prompt, input_default(step, &self.defaults, vars)),
+                };
+                vars.insert(step.var.clone(), answer);
+            }
+            Step::Select(step) => {
+                let labels = step.labels(vars);
+                let prompt = render(&step.prompt, vars);
+                // Validation rules this out for lessons keyed by known variables
+                if labels.is_empty() {
+                    let problem = format!("Error: the lesson has no options for '{}' here; skipping it.", prompt);
+                    frontend.println(&problem.themed(Role::Error).to_string());
+                    return ControlFlow::Continue(());
+                }
+                let items: Vec<&str> = labels.iter().map(String::as_str).collect();
+                let default = select_default(step, &labels, &self.defaults);
+                let selection = match self.answers.get(&step.var).map(|answer| preset_choice(step, &labels, answer)) {
+                    Some(Ok(index)) => {
+                        frontend.println(&format!("{}: {}", prompt, labels[index]));
+                        index
+                    }
+                    Some(Err(problem)) => {
+                        show_warning(frontend, &problem);
+                        frontend.select(&prompt, &items, default)
+                    }
+                    None => frontend.select(&prompt, &items, default),
+                };
+                vars.insert(step.var.clone(), step.value(&labels, selection));
+                vars.insert(format!("{}_label", step.var), labels[selection].clone());
+            }
+            Step::Password(step) => {
+                let username = render(&step.user, vars);
+                return self.set_password(&username, wiki);
+            }
+            Step::Message(step) => {
+                show_message(frontend, &render(&step.text, vars), &step.style);
+                frontend.pause(step.pause);
+            }
+            Step::Pause(step) => frontend.pause(step.secs),
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Runs a command step until the expected command is accepted or skipped,
+    /// then shows what the real command would print
+    fn run_command_step(&mut self, step: &CommandStep, wiki: Option<&str>) -> ControlFlow<Jump> {
+        let command = render(&step.command, &self.vars);
+        let description = render(&step.description, &self.vars);
+        let guide = step.guide.as_ref().map(|guide| render(guide, &self.vars));
+        let answers: Vec<String> = step.answers.iter().map(|answer| render(answer, &self.vars)).collect();
+        let target = CommandTarget {
+            command: &command,
+            description: &description,
+            guide: guide.as_deref(),
+            check: step.check.as_ref(),
+            rules: &step.rules,
+            answers: &answers,
+        };
+        let mut record = StepRecord::new(&description, wiki);
+        let started = self.frontend.elapsed();
+        self.simulate_command(&target, step.chroot, &mut record)?;
+        record.time = self.frontend.elapsed() - started;
+        self.report.steps.push(record);
+        if let Some(output) = &step.output {
+            self.frontend.println(&render(output, &self.vars));
+        }
+        if let Some(progress) = &step.progress {
+            show_progress(self.frontend, progress, &self.vars);
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Prompts until the expected command is accepted. Breaks if a lesson
+    /// command leaves the step; every `max_attempts` wrong attempts, offers
+    /// a way out.
+    fn simulate_command(&mut self, target: &CommandTarget, in_chroot: bool, record: &mut StepRecord) -> ControlFlow<Jump> {
+        let command = target.command;
+        let options = self.options;
+        // The last line that was not accepted, and the attempts when help was last offered
+        let mut last = String::new();
+        let mut offered = 0;
+
+        loop {
+            if options.max_attempts > 0 && record.attempts >= offered + options.max_attempts {
+                offered = record.attempts;
+                let hidden = free_hints(options) + record.hints < hint::LEVELS;
+                match self.offer_rescue(record, hidden) {
+                    Some(Rescue::Answer) => record.hints = hint::LEVELS - free_hints(options),
+                    Some(Rescue::Explanation) => self.frontend.println(&hint::explain(command, &last).themed(Role::Warning).to_string()),
+                    Some(Rescue::Skip) => {
+                        self.skip(target, record);
+                        return ControlFlow::Continue(());
+                    }
+                    None => {}
+                }
+            }
+            match options.mode {
+                Mode::Exam => self.frontend.println(&format!("\n# {} {}", target.description.themed(Role::Header), clock(self.frontend))),
+                _ => self.frontend.println(&format!("\n# {}", target.description.themed(Role::Header))),
+            }
+            show_hint(self.frontend, target, options, record);
+
+            // `cd` shows in the prompt, as the home directory `~`
+            let cwd = match self.system.vfs.cwd() {
+                "/root" => "~",
+                dir => dir,
+            };
+            let prompt = if in_chroot {
+                format!("{}:{} # ", "root".themed(Role::User), cwd.themed(Role::Header))
+            } else {
+                format!("{}@archiso {} # ", "root".themed(Role::User), cwd.themed(Role::Success))
+            };
+            // Tab only completes the expected command once the hint shows all of it
+            let shown = free_hints(options) + record.hints >= hint::LEVELS;
+            let completer = StepCompleter { system: &self.system, expected: shown.then_some(command) };
+            let input = match self.frontend.read_command(&prompt, &completer) {
+                Ok(input) => input,
+                // Ctrl-D on an empty line exits, as in bash: the chroot on the
+                // step that asks for it, else the lesson
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.frontend.println("exit");
+                    if command != "exit" {
+                        return ControlFlow::Break(Jump::Quit);
+                    }
+                    "exit".to_string()
+                }
+                // Anything else would fail again on every retry
+                Err(err) => {
+                    show_warning(self.frontend, &format!("Warning: cannot read input: {}", err));
+                    return ControlFlow::Break(Jump::Quit);
+                }
+            };
+
+            let line = input.trim();
+            if line.strip_prefix(':').is_some_and(|name| name.starts_with(|c: char| c.is_ascii_alphabetic())) {
+                match self.lesson_command(line, target, record) {
+                    Some(flow) => return flow,
+                    None => continue,
+                }
+            }
+
+            let script = match shell::parse(&input) {
+                Ok(script) => script,
+                Err(err) => {
+                    record.attempts += 1;
+                    self.frontend.println(&err.themed(Role::Error).to_string());
+                    last = line.to_string();
+                    continue;
+                }
+            };
+            let system = &self.system;
+            let commands = script.commands(&|name| system.var(name));
+            let is_target = |c: &CommandLine| command::matches(c, command, target.rules);
+            let typed_target = commands.iter().any(is_target);
+            // Other commands on the line may only look around, not change the machine
+            let harmless = commands.iter().all(|c| is_target(c) || System::is_inspection(c));
+
+            match line {
+                _ if typed_target && harmless => {
+                    record.attempts += 1;
+                    let run = run_script(&mut self.system, self.frontend, &script);
+                    // Accepted variants may differ from the canonical form, so check what actually ran
+                    if run.ran.iter().any(|(c, ok)| *ok && is_target(c)) {
+                        match target.check.map(|check| self.system.verify(check)) {
+                            Some(Err(problem)) => self.frontend.println(&problem.themed(Role::Error).to_string()),
+                            _ => return ControlFlow::Continue(()),
+                        }
+                    }
+                }
+                "clear" => {
+                    self.frontend.clear_screen();
+                    continue;
+                }
+                _ if harmless => {
+                    run_script(&mut self.system, self.frontend, &script);
+                    continue;
+                }
+                _ => {
+                    record.attempts += 1;
+                    match diagnose::error(&self.system, &script) {
+                        Some(error) => self.frontend.println(&error.themed(Role::Error).to_string()),
+                        None => {
+                            let problem = format!("That would run, but it is not this step: {}", target.description);
+                            self.frontend.println(&problem.themed(Role::Warning).to_string());
+                        }
+                    }
+                    if line == "exit" {
+                        self.frontend.println(&"To leave the lesson, type :quit.".themed(Role::Warning).to_string());
+                    }
+                }
+            }
+            last = line.to_string();
+        }
+    }
+
+    /// Offers a way out once a step's attempts run out: the answer if
+    /// `answer` allows it and the mode has hints, an explanation, or a skip.
+    /// Records and returns the choice; `None` means keep trying.
+    fn offer_rescue(&mut self, record: &mut StepRecord, answer: bool) -> Option<Rescue> {
+        let mut choices = Vec::new();
+        if self.options.mode != Mode::Exam {
+            if answer {
+                choices.push((Some(Rescue::Answer), "Show me the answer"));
+            }
+            choices.push((Some(Rescue::Explanation), "Explain what went wrong"));
+        }
+        choices.extend([(Some(Rescue::Skip), "Skip this step"), (None, "Keep trying")]);
+        let labels: Vec<&str> = choices.iter().map(|(_, label)| *label).collect();
+        let prompt = format!("\n{} wrong attempts. What now?", record.attempts);
+        let rescue = choices[self.frontend.select(&prompt, &labels, 0)].0;
+        if rescue.is_some() {
+            record.rescue = rescue;
+        }
+        rescue
+    }
+
+    /// Does the step for the learner, for no points
+    fn skip(&mut self, target: &CommandTarget, record: &mut StepRecord) {
+        record.skipped = true;
+        run_silently(&mut self.system, target.command, target.answers);
+        show_warning(self.frontend, &format!("Skipped; the step was done with: {}", target.command));
+    }
+
+    /// Helper: Sets password for user, asking again until both entries
+    /// match, and records the step like a command step. Breaks if the input
+    /// ends.
+    fn set_password(&mut self, username: &str, wiki: Option<&str>) -> ControlFlow<Jump> {
+        let mut record = StepRecord::new(&format!("Set password for {}", username), wiki);
+        let started = self.frontend.elapsed();
+        let max_attempts = self.options.max_attempts;
+        loop {
+            if max_attempts > 0 && record.attempts > 0 && record.attempts.is_multiple_of(max_attempts) {
+                match self.offer_rescue(&mut record, false) {
+                    Some(Rescue::Explanation) => {
+                        let explanation = "Type the same password twice, and not an empty one; nothing is shown while you type.";
+                        self.frontend.println(&explanation.themed(Role::Warning).to_string());
+                    }
+                    Some(Rescue::Skip) => {
+                        record.skipped = true;
+                        show_warning(self.frontend, &format!("Skipped; {} has no password yet.", username));
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            self.frontend.println(&format!("\n{}", format!("Set password for {}:", username).themed(Role::Header)));
+            // Ctrl-D leaves the lesson, as at the shell prompt
+            let entries = self.frontend.password("New password: ").and_then(|pwd1| Ok((pwd1, self.frontend.password("Retype password: ")?)));
+            let Ok((pwd1, pwd2)) = entries else {
+                self.frontend.println("");
+                return ControlFlow::Break(Jump::Quit);
+            };
+            record.attempts += 1;
+            if pwd1 == pwd2 && !pwd1.is_empty() {
+                self.frontend.println(&"Password updated.".themed(Role::Success).to_string());
+                break;
+            }
+            self.frontend.println(&"Passwords don't match or empty.".themed(Role::Error).to_string());
+        }
+        record.time = self.frontend.elapsed() - started;
+        self.report.steps.push(record);
+        ControlFlow::Continue(())
+    }
+
+    /// Answers a lesson command such as `:hint`, which never counts as an
+    /// attempt. Returns how the step ends if the command ends it.
+    fn lesson_command(&mut self, name: &str, target: &CommandTarget, record: &mut StepRecord) -> Option<ControlFlow<Jump>> {
+        match name {
+            ":hint" => request_hint(self.frontend, self.options, record, 1),
+            ":answer" => request_hint(self.frontend, self.options, record, hint::LEVELS),
+            ":skip" => {
+                self.skip(target, record);
+                return Some(ControlFlow::Continue(()));
+            }
+            ":back" if self.snapshots.len() < 2 => {
+                self.frontend.println(&"There is no earlier step to go back to.".themed(Role::Warning).to_string());
+            }
+            ":back" => return Some(ControlFlow::Break(Jump::Back)),
+            ":menu" if self.scenario.chapters().next().is_none() => {
+                self.frontend.println(&"This lesson has no chapters.".themed(Role::Warning).to_string());
+            }
+            ":menu" => {
+                if let Some(chapter) = self.choose_chapter(Some("Stay at this step")) {
+                    return Some(ControlFlow::Break(Jump::Chapter(chapter)));
+                }
+            }
+            ":save" => match self.checkpoint {
+                Some(path) => {
+                    self.save(self.at);
+                    if self.checkpoint.is_some() {
+                        self.frontend.println(&format!("Progress saved to {}.", path.display()));
+                    }
+                }
+                None => self.frontend.println(&"Saving is off in this run.".themed(Role::Warning).to_string()),
+            },
+            ":quit" => return Some(ControlFlow::Break(Jump::Quit)),
+            ":help" => {
+                for (command, summary) in LESSON_COMMANDS {
+                    self.frontend.println(&format!("  {} {}", format!("{:<8}", command).themed(Role::Hint), summary));
+                }
+            }
+            _ => {
+                let problem = format!("Unknown lesson command '{}'. Type :help for the list.", name);
+                self.frontend.println(&problem.themed(Role::Error).to_string());
+            }
+        }
+        None
+    }
+
+    /// Asks which chapter to go to among those that apply to the chosen
+    /// machine and returns its stage id; `None` if the learner picks `stay`,
+    /// an extra first option, or if no chapter applies
+    fn choose_chapter(&mut self, stay: Option<&str>) -> Option<String> {
+        let chapters: Vec<&Stage> = self.scenario.chapters().filter(|stage| stage.condition.holds(&self.vars)).collect();
+        let mut titles: Vec<&str> = stay.into_iter().collect();
+        titles.extend(chapters.iter().filter_map(|stage| stage.chapter.as_deref()));
+        if titles.is_empty() {
+            return None;
+        }
+        let choice = self.frontend.select("Start at which chapter?", &titles, 0);
+        choice.checked_sub(usize::from(stay.is_some())).map(|index| chapters[index].id.clone())
+    }
+
+    /// Fast-forwards the stages before `chapter` and returns its index, or 0
+    /// if the lesson has no such chapter
+    fn skip_to(&mut self, chapter: &str) -> usize {
+        let scenario = self.scenario;
+        let Some(index) = scenario.stages.iter().position(|stage| stage.id == chapter) else {
+            show_warning(self.frontend, &format!("The lesson has no chapter '{}'; starting at the beginning.", chapter));
+            return 0;
+        };
+        for stage in &scenario.stages[..index] {
+            if stage.condition.holds(&self.vars) {
+                self.fast_forward(stage);
+            }
+        }
+        let title = scenario.stages[index].chapter.as_deref().unwrap_or(chapter);
+        show_warning(self.frontend, &format!("Starting at chapter '{}'; the steps before it were done for you with default answers.", title));
+        index
+    }
+
+    /// Does a stage's steps without showing anything, as a learner taking every
+    /// default would, so that later chapters find the machine set up
+    fn fast_forward(&mut self, stage: &Stage) {
+        for step in &stage.steps {
+            if step.condition().holds(&self.vars) {
+                self.skip_step(step);
+                self.resolve_derived();
+            }
+        }
+    }
 
-/// Simulates final installation steps
-fn simulate_final_steps() {
-    show_header("Finishing installation...");
-
-    // Generate fstab
-    simulate_command("genfstab -U /mnt >> /mnt/etc/fstab", "Generate fstab", false, || {
-        println!("fstab generated.");
-    });
-
-    // Enter chroot
-    simulate_command("arch-chroot /mnt", "Enter chroot environment", false, || {
-        println!("Now in chroot environment.");
-    });
-
-    // Configure system
-    configure_hostname();
-    configure_timezone();
-    configure_locale();
-    configure_users();
-    configure_bootloader();
-    install_desktop();
-
-    // Exit and reboot
-    simulate_command("exit", "Exit chroot", true, || {
-        println!("Exited chroot.");
-    });
-
-    simulate_command("umount -R /mnt", "Unmount partitions", false, || {
-        println!("Partitions unmounted.");
-    });
-
-    simulate_command("reboot", "Reboot system", false, || {
-        println!("System rebooting...");
-    });
-
-    show_success("Installation complete!");
-    println!("{}", "You've learned the basics of Arch Linux installation.".bright_green());
-    println!("{}", "For complete guide: https://wiki.archlinux.org/title/Installation_guide".bright_blue());
+    fn skip_step(&mut self, step: &Step) {
+        let vars = &mut self.vars;
+        match step {
+            Step::Command(step) => {
+                let answers: Vec<String> = step.answers.iter().map(|answer| render(answer, vars)).collect();
+                run_silently(&mut self.system, &render(&step.command, vars), &answers);
+            }
+            Step::Input(step) => {
+                let answer = self.answers.get(&step.var).cloned()
+                    .or_else(|| input_default(step, &self.defaults, vars))
+                    .or_else(|| step.example.as_ref().map(|example| render(example, vars)))
+                    .unwrap_or_default();
+                vars.insert(step.var.clone(), answer);
+            }
+            Step::Select(step) => {
+                let labels = step.labels(vars);
+                if labels.is_empty() {
+                    return;
+                }
+                let selection = self.answers.get(&step.var)
+                    .and_then(|answer| preset_choice(step, &labels, answer).ok())
+                    .unwrap_or_else(|| select_default(step, &labels, &self.defaults));
+                vars.insert(step.var.clone(), step.value(&labels, selection));
+                vars.insert(format!("{}_label", step.var), labels[selection].clone());
+            }
+            Step::Password(_) | Step::Message(_) | Step::Pause(_) => {}
+        }
+    }
 }
 
-/// Configures system hostname
-fn configure_hostname() {
-    show_header("Configuring system hostname...");
-    
-    let hostname = Input::new()
-        .with_prompt("Enter hostname")
-        .default("archlinux".to_string())
-        .interact()
-        .unwrap();
-    
-    simulate_command(
-        &format!("echo \"{}\" > /etc/hostname", hostname),
-        "Set hostname",
-        true,
-        || println!("Hostname set to {}", hostname.bright_green()),
-    );
-    
-    // Simplified hosts file configuration
-    simulate_command(
-        &format!("echo '127.0.1.1 {}' >> /etc/hosts", hostname),
-        "Configure hosts file (simplified)",
-        true,
-        || println!("/etc/hosts configured with hostname"),
-    );
+/// Asks which chapter to start at, offering those that apply to the chosen
+/// machine, and returns its stage id; `None` if none of them apply
+pub fn choose_chapter(scenario: &Scenario, user_choices: &UserChoices, frontend: &mut dyn Frontend) -> Option<String> {
+    let options = Options::default();
+    let mut simulation = Simulation::new(scenario, user_choices, &options, frontend);
+    simulation.choose_chapter(None)
 }
 
-/// Configures system timezone
-fn configure_timezone() {
-    show_header("Configuring timezone...");
-    
-    let regions = &["Africa", "America", "Asia", "Europe", "Australia"];
-    let region = regions[Select::new()
-        .with_prompt("Select continent")
-        .items(regions)
-        .default(2)
-        .interact()
-        .unwrap()];
-    
-    let cities = match region {
-        "Africa" => vec!["Cairo", "Johannesburg", "Nairobi"],
-        "America" => vec!["New_York", "Chicago", "Los_Angeles"],
-        "Asia" => vec!["Riyadh", "Dubai", "Tokyo"],
-        "Europe" => vec!["London", "Paris", "Berlin"],
-        "Australia" => vec!["Sydney", "Melbourne"],
-        _ => vec!["UTC"],
-    };
-    
-    let city = cities[Select::new()
-        .with_prompt("Select city")
-        .items(&cities)
-        .interact()
-        .unwrap()];
-    
-    let timezone = format!("{}/{}", region, city);
-    
-    simulate_command(
-        &format!("ln -sf /usr/share/zoneinfo/{} /etc/localtime", timezone),
-        "Set timezone",
-        true,
-        || println!("Timezone set to {}", timezone.bright_green()),
-    );
-
-    simulate_command("hwclock --systohc", "Sync hardware clock", true, || {
-        println!("Hardware clock synced.");
-    });
+/// Asks an input step's question until the answer is not a lesson command,
+/// which would otherwise be stored as the value
+fn ask(frontend: &mut dyn Frontend, prompt: &str, default: Option<String>) -> String {
+    loop {
+        let answer = frontend.input(prompt, default.clone());
+        if !answer.starts_with(':') {
+            return answer;
+        }
+        let problem = "Lesson commands such as :quit only work at the shell prompt; answer the question first.";
+        frontend.println(&problem.themed(Role::Warning).to_string());
+    }
 }
 
-/// Configures system locale
-fn configure_locale() {
-    show_header("Configuring system locale...");
-    
-    // Use fixed English locale only
-    let locale = "en_US.UTF-8 UTF-8";
-    
-    simulate_command(
-        &format!("sed -i 's/^#{}//' /etc/locale.gen", locale),
-        "Uncomment locale",
-        true,
-        || println!("Uncommented {}", locale.bright_green()),
-    );
-    
-    simulate_command("locale-gen", "Generate locales", true, || {
-        println!("Locales generated.");
-    });
-    
-    let lang = locale.split_whitespace().next().unwrap();
-    simulate_command(
-        &format!("echo \"LANG={}\" > /etc/locale.conf", lang),
-        "Set system language",
-        true,
-        || println!("Language set to {}", lang.bright_green()),
-    );
+/// Answer an input dialog offers: the config file's, else the step's own
+fn input_default(step: &InputStep, defaults: &HashMap<String, String>, vars: &HashMap<String, String>) -> Option<String> {
+    defaults.get(&step.var).cloned().or_else(|| step.default.as_ref().map(|default| render(default, vars)))
 }
 
-/// Configures system users
-fn configure_users() {
-    show_header("Configuring users...");
-    
-    set_password("root");
-
-    let username: String = Input::new()
-        .with_prompt("Enter username for new user")
-        .interact()
-        .unwrap();
-    
-    simulate_command(&format!("useradd -mG wheel {}", username), "Create user", true, || {
-        println!("User {} created.", username);
-    });
-    
-    set_password(&username);
+/// Option a select dialog preselects: the config file's, else the step's own
+fn select_default(step: &SelectStep, labels: &[String], defaults: &HashMap<String, String>) -> usize {
+    defaults.get(&step.var)
+        .and_then(|answer| preset_choice(step, labels, answer).ok())
+        .unwrap_or(step.default.min(labels.len().saturating_sub(1)))
 }
 
-/// Configures bootloader
-fn configure_bootloader() {
-    show_header("Configuring bootloader...");
-    
-    simulate_command(
-        "grub-install --target=x86_64-efi --efi-directory=/boot/efi --bootloader-id=GRUB",
-        "Install GRUB",
-        true,
-        || println!("GRUB installed."),
-    );
-
-    simulate_command(
-        "grub-mkconfig -o /boot/grub/grub.cfg",
-        "Generate GRUB config",
-        true,
-        || println!("GRUB config generated."),
-    );
+/// Finds the option a command-line answer names, by value or label
+fn preset_choice(step: &SelectStep, labels: &[String], answer: &str) -> Result<usize, String> {
+    (0..labels.len())
+        .find(|&index| step.value(labels, index).eq_ignore_ascii_case(answer) || labels[index].eq_ignore_ascii_case(answer))
+        .ok_or_else(|| format!("'{}' is not one of: {}", answer, labels.join(", ")))
 }
 
-/// Installs desktop environment
-fn install_desktop() {
-    show_header("Installing desktop environment...");
-    
-    let options = &["GNOME", "Plasma", "Hyprland", "None"];
-    let selection = Select::new()
-        .with_prompt("Choose desktop environment")
-        .items(options)
-        .interact()
-        .unwrap();
-
-    if selection == 3 {
-        println!("\n{}", "Skipping desktop installation.".bright_yellow());
-        return;
+/// Prints a message step in its style
+fn show_message(frontend: &mut dyn Frontend, text: &str, style: &MessageStyle) {
+    match style {
+        MessageStyle::Header => show_header(frontend, text),
+        MessageStyle::Success => show_success(frontend, text),
+        MessageStyle::Warning => show_warning(frontend, text),
+        MessageStyle::Info => frontend.println(&format!("\n{}", text.themed(Role::Success))),
+        MessageStyle::Notice => frontend.println(&format!("\n{}", text.themed(Role::Warning))),
+        MessageStyle::Link => frontend.println(&text.themed(Role::Header).to_string()),
+        MessageStyle::Text => frontend.println(text),
     }
+}
 
-    let package = match selection {
-        0 => "gnome",
-        1 => "plasma",
-        2 => "hyprland",
-        _ => return,
-    };
+/// Animates a progress bar for long-running commands
+fn show_progress(frontend: &mut dyn Frontend, progress: &Progress, vars: &HashMap<String, String>) {
+    frontend.progress(progress.ticks, &render(&progress.message, vars), &render(&progress.done, vars));
+}
 
-    simulate_command(&format!("pacman -S {}", package), &format!("Install {}", options[selection]), true, || {
-        let pb = create_progress_bar(7);
-        for i in 0..7 {
-            pb.set_position(i);
-            pb.set_message(format!("Installing {}...", options[selection]));
-            sleep(1);
+/// Prints a command line's stdout, and its stderr in red
+fn print_run(frontend: &mut dyn Frontend, run: &Run) {
+    for result in &run.output {
+        match result {
+            Ok(stdout) => frontend.print(stdout),
+            Err(stderr) if stderr.is_empty() => {}
+            Err(stderr) => frontend.println(&stderr.themed(Role::Error).to_string()),
         }
-        pb.finish_with_message("Installation complete.");
-    });
+    }
 }
 
-/// Helper: Sets password for user
-fn set_password(username: &str) {
-    println!("\n{}", format!("Set password for {}:", username).bright_blue());
-    
-    // Skip command simulation for password setting
-    let pwd1 = read_password_with_prompt("New password: ");
-    let pwd2 = read_password_with_prompt("Retype password: ");
-    
-    if pwd1 == pwd2 && !pwd1.is_empty() {
-        println!("{}", "Password updated.".bright_green());
-    } else {
-        println!("{}", "Passwords don't match or empty.".red());
-        set_password(username); // Retry if passwords don't match
+/// Runs a command line with no output, feeding interactive programs `answers`
+fn run_silently(system: &mut System, command: &str, answers: &[String]) {
+    if let Ok(script) = shell::parse(command) {
+        system.run(&script, &mut Memory::new(answers.to_vec()));
     }
 }
 
-/// Helper: Reads password with prompt
-fn read_password_with_prompt(prompt: &str) -> String {
-    print!("{}", prompt);
-    std::io::stdout().flush().unwrap();
-    rpassword::read_password().unwrap_or_default()
+/// Runs a command line, letting interactive programs prompt through the frontend
+fn run_script(system: &mut System, frontend: &mut dyn Frontend, script: &Script) -> Run {
+    let run = system.run(script, frontend);
+    print_run(frontend, &run);
+    run
 }
 
-/// Helper: Simulates command execution
-fn simulate_command<F>(command: &str, description: &str, in_chroot: bool, action: F)
-where
-    F: FnOnce(),
-{
-    println!("\n# {}", description.bright_blue());
-    println!("[hint] type: {}", command.bright_cyan());
-    
-    let prompt = if in_chroot {
-        format!("{}:{} # ", "root".bright_red(), "/".bright_blue())
-    } else {
-        format!("{}@archiso {} # ", "root".bright_red(), "~".bright_green())
-    };
+/// What the user has to type at a command step
+struct CommandTarget<'a> {
+    command: &'a str,
+    description: &'a str,
+    guide: Option<&'a str>,
+    check: Option<&'a Check>,
+    rules: &'a MatchRules,
+    /// Lines fed to interactive programs when the step is skipped
+    answers: &'a [String],
+}
 
-    let input = read_input_with_history(&prompt).unwrap_or_default();
+/// Hint levels the mode reveals before anything is asked for
+fn free_hints(options: &Options) -> u32 {
+    match (options.mode, options.hints) {
+        (Mode::Guided, HintLevel::Full) => hint::LEVELS,
+        (Mode::Guided, HintLevel::Partial) => 1,
+        _ => 0,
+    }
+}
 
+/// Shows as much of the expected command as the mode, the hints asked for
+/// and, in practice mode, the mistakes so far allow
+fn show_hint(frontend: &mut dyn Frontend, target: &CommandTarget, options: &Options, record: &mut StepRecord) {
+    if options.mode == Mode::Practice {
+        record.hints = record.hints.max(record.attempts / REVEAL_AFTER).min(hint::LEVELS);
+    }
+    let level = free_hints(options) + record.hints;
+    let label = match level {
+        0 => return,
+        1 => "use",
+        2 => "flags",
+        _ => "type",
+    };
+    frontend.println(&format!("[hint] {}: {}", label, hint::reveal(target.command, level).themed(Role::Hint)));
+    if let Some(guide) = target.guide {
+        frontend.println(&guide.themed(Role::Warning).to_string());
+    }
+}
 
-    match input.trim() {
-        x if x == command => {
-            action();
-        }
-        "clear" => {
-            clear_screen();
-            simulate_command(command, description, in_chroot, action);
-        }
-        "exit" => {
-            std::process::exit(0);
-        }
-        _ => {
-            println!("{}", "Error: Invalid command. Try again.".red());
-            simulate_command(command, description, in_chroot, action);
-        }
+/// Answers `:hint` and `:answer` by revealing up to `levels` more, except
+/// in exam mode
+fn request_hint(frontend: &mut dyn Frontend, options: &Options, record: &mut StepRecord, levels: u32) {
+    let hidden = hint::LEVELS - free_hints(options);
+    if options.mode == Mode::Exam {
+        frontend.println(&"Hints are not available in exam mode.".themed(Role::Warning).to_string());
+    } else if record.hints < hidden {
+        record.hints = (record.hints + levels).min(hidden);
+    } else {
+        frontend.println(&"The hint already shows the whole command.".themed(Role::Warning).to_string());
     }
 }
+
+/// Minutes and seconds since the lesson started, shown on exam steps
+fn clock(frontend: &dyn Frontend) -> String {
+    let secs = frontend.elapsed().as_secs();
+    format!("[{:02}:{:02}]", secs / 60, secs % 60)
+}
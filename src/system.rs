@@ -0,0 +1,809 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::command::{CommandLine, Options};
+use crate::disk::{human_size, parse_size, render_blkid, render_fdisk_list, render_lsblk, Disk, Partition, SECTOR_SIZE};
+use crate::fdisk::{self, Outcome};
+use crate::frontend::Frontend;
+use crate::scenario::Check;
+use crate::shell::{fold_tee, Connector, Script};
+use crate::vfs::{Node, Vfs, VfsError};
+
+/// Time zones shipped by the simulated `tzdata` package
+const ZONEINFO: &[&str] = &[
+    "UTC",
+    "Africa/Cairo", "Africa/Johannesburg", "Africa/Nairobi",
+    "America/New_York", "America/Chicago", "America/Los_Angeles",
+    "Asia/Riyadh", "Asia/Dubai", "Asia/Tokyo",
+    "Europe/London", "Europe/Paris", "Europe/Berlin",
+    "Australia/Sydney", "Australia/Melbourne",
+];
+
+/// Locales listed (commented out) in a fresh /etc/locale.gen
+const LOCALES: &[&str] = &[
+    "ar_SA.UTF-8 UTF-8",
+    "de_DE.UTF-8 UTF-8",
+    "en_GB.UTF-8 UTF-8",
+    "en_US.UTF-8 UTF-8",
+    "fr_FR.UTF-8 UTF-8",
+    "ja_JP.UTF-8 UTF-8",
+];
+
+/// Commands that leave the machine untouched and may be run at any prompt
+const INSPECTION_COMMANDS: &[&str] = &[
+    "ls", "cat", "mount", "lsblk", "blkid", "echo", "grep", "head", "tail", "wc",
+    "printenv", "env", "export", "unset", "true", "false", "cd", "pwd",
+];
+
+/// Programs the simulated shell knows, offered by Tab completion
+pub const COMMANDS: &[&str] = &[
+    "arch-chroot", "blkid", "cat", "cd", "clear", "echo", "env", "exit", "export", "false", "fdisk",
+    "genfstab", "grep", "grub-install", "grub-mkconfig", "head", "hwclock", "ip", "iwctl", "ln",
+    "locale-gen", "ls", "lsblk", "mkdir", "mkfs.ext4", "mkfs.fat", "mkfs.vfat", "mkswap", "mount",
+    "pacman", "pacstrap", "passwd", "printenv", "pwd", "reboot", "sed", "tail", "tee", "true",
+    "umount", "unset", "useradd", "wc",
+];
+
+/// Packages in the simulated repositories, offered by Tab completion
+pub const PACKAGES: &[&str] = &[
+    "amd-ucode", "base", "base-devel", "efibootmgr", "git", "gnome", "grub", "hyprland",
+    "intel-ucode", "iwd", "linux", "linux-firmware", "linux-headers", "linux-lts", "man-db",
+    "mesa", "nano", "networkmanager", "nvidia", "nvidia-utils", "plasma", "sudo", "vim",
+];
+
+/// Environment of the live ISO's root shell
+const ENVIRONMENT: &[(&str, &str)] = &[
+    ("HOME", "/root"),
+    ("LANG", "C.UTF-8"),
+    ("PATH", "/usr/local/sbin:/usr/local/bin:/usr/bin"),
+    ("SHELL", "/bin/bash"),
+    ("TERM", "linux"),
+    ("USER", "root"),
+];
+
+/// Size of the blank disk the lesson installs onto
+const TARGET_DISK_BYTES: u64 = 50 * 1024 * 1024 * 1024;
+
+/// Simulated machine: everything the lesson's commands can change
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct System {
+    pub vfs: Vfs,
+    pub disks: Vec<Disk>,
+    /// Shell variables, all exported
+    pub env: BTreeMap<String, String>,
+    /// Exit status of the last pipeline, as `$?`
+    status: i32,
+}
+
+/// What a command line did
+#[derive(Debug, Default)]
+pub struct Run {
+    /// Standard output and errors in the order they were printed
+    pub output: Vec<Result<String, String>>,
+    /// Simple commands that ran, with whether their pipeline succeeded
+    pub ran: Vec<(CommandLine, bool)>,
+}
+
+impl System {
+    /// Creates the live ISO environment with a blank target disk
+    pub fn new() -> Self {
+        let mut vfs = Vfs::new();
+        for dir in ["/mnt", "/root", "/etc", "/usr/share/zoneinfo"] {
+            let _ = vfs.mkdir_p(dir);
+        }
+        let _ = vfs.write("/etc/hostname", "archiso\n", false);
+        let disks = vec![Disk::new("sdz", "ArchTry Virtual Disk", TARGET_DISK_BYTES)];
+        let env = ENVIRONMENT.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        System { vfs, disks, env, status: 0 }
+    }
+
+    /// Returns true for read-only commands the user may run between steps,
+    /// and for `mkdir`, as no step needs a directory to be missing
+    pub fn is_inspection(command: &CommandLine) -> bool {
+        match command.program() {
+            _ if command.redirect.is_some() => false,
+            Some("mkdir") => true,
+            Some("fdisk") => Options::parse("fdisk", &command.args()).has("list"),
+            Some("mount") => command.words.len() == 1,
+            Some(program) => INSPECTION_COMMANDS.contains(&program),
+            None => true,
+        }
+    }
+
+    /// Returns the disk with the given device path
+    pub fn disk(&self, path: &str) -> Option<&Disk> {
+        self.disks.iter().find(|d| d.path() == path)
+    }
+
+    /// Returns true if `path` names an existing disk or partition
+    pub fn block_device_exists(&self, path: &str) -> bool {
+        self.disks.iter().any(|d| d.path() == path || d.partition_number(path).is_some())
+    }
+
+    /// Checks the state a step requires, describing the first mismatch
+    pub fn verify(&self, check: &Check) -> Result<(), String> {
+        for (device, label) in &check.label {
+            let found = self.disk(device).and_then(|d| d.label).map(|l| l.to_string());
+            if found.as_deref() != Some(label.as_str()) {
+                return Err(format!(
+                    "{} should have a {} partition table, found {}.",
+                    device, label, found.as_deref().unwrap_or("none")
+                ));
+            }
+        }
+        for (device, expected) in &check.partition_type {
+            let partition = self.partition(device).ok_or(format!("{} does not exist.", device))?;
+            if partition.kind.name != expected {
+                return Err(format!("{} should be of type '{}', found '{}'.", device, expected, partition.kind.name));
+            }
+        }
+        for (device, size) in &check.min_size {
+            let partition = self.partition(device).ok_or(format!("{} does not exist.", device))?;
+            let minimum = parse_size(size).unwrap_or(0);
+            let actual = partition.sectors() * SECTOR_SIZE;
+            if actual < minimum {
+                return Err(format!("{} should be at least {}, found {}.", device, size, human_size(actual)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the partition behind a device path
+    fn partition(&self, path: &str) -> Option<&Partition> {
+        self.disks.iter().find_map(|d| d.partition_number(path).and_then(|n| d.partition(n)))
+    }
+
+    /// Returns a shell variable; `?` is the last exit status
+    pub fn var(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.status.to_string()),
+            _ => self.env.get(name).cloned(),
+        }
+    }
+
+    /// Runs a parsed command line against the simulated state.
+    /// Interactive programs such as fdisk talk to the user through `frontend`.
+    pub fn run(&mut self, script: &Script, frontend: &mut dyn Frontend) -> Run {
+        let mut run = Run::default();
+        for (connector, pipeline) in script.pipelines() {
+            let proceed = match connector {
+                Connector::Always => true,
+                Connector::And => self.status == 0,
+                Connector::Or => self.status != 0,
+            };
+            if !proceed {
+                continue;
+            }
+            // Expand late so earlier pipelines' assignments are visible
+            let commands = pipeline.expand(&|name| self.var(name));
+            let mut stdin = String::new();
+            let mut status = 0;
+            for command in &commands {
+                match self.run_simple(command, &stdin, frontend) {
+                    Ok(stdout) => {
+                        stdin = stdout;
+                        status = 0;
+                    }
+                    Err(stderr) => {
+                        run.output.push(Err(stderr));
+                        stdin.clear();
+                        status = 1;
+                    }
+                }
+            }
+            if !stdin.is_empty() {
+                run.output.push(Ok(stdin));
+            }
+            self.status = status;
+            run.ran.extend(fold_tee(commands).into_iter().map(|command| (command, status == 0)));
+        }
+        run
+    }
+
+    /// Runs one command of a pipeline, feeding it the previous command's output
+    fn run_simple(&mut self, command: &CommandLine, stdin: &str, frontend: &mut dyn Frontend) -> Result<String, String> {
+        let stdout = match command.program() {
+            Some(program) => self.execute(program, &command.args(), stdin, frontend)?,
+            None => {
+                for (name, value) in &command.assignments {
+                    self.env.insert(name.clone(), value.clone());
+                }
+                String::new()
+            }
+        };
+        match &command.redirect {
+            Some(redirect) => {
+                self.vfs
+                    .write(&redirect.path, &stdout, redirect.append)
+                    .map_err(|err| format!("bash: {}: {}", redirect.path, err))?;
+                Ok(String::new())
+            }
+            None => Ok(stdout),
+        }
+    }
+
+    /// Dispatches a single program invocation
+    fn execute(&mut self, program: &str, args: &[&str], stdin: &str, frontend: &mut dyn Frontend) -> Result<String, String> {
+        match program {
+            "echo" => Ok(format!("{}\n", args.join(" "))),
+            "cat" if args.is_empty() => Ok(stdin.to_string()),
+            "cat" => self.cat(args),
+            "grep" => self.grep(args, stdin),
+            "head" | "tail" => self.head_tail(program, args, stdin),
+            "wc" => self.wc(args, stdin),
+            "tee" => self.tee(args, stdin),
+            "export" => Ok(self.export(args)),
+            "unset" => {
+                for name in args {
+                    self.env.remove(*name);
+                }
+                Ok(String::new())
+            }
+            "printenv" | "env" => self.printenv(args),
+            "cd" => self.cd(args),
+            "pwd" => Ok(format!("{}\n", self.vfs.cwd())),
+            "mkdir" => self.mkdir(args),
+            "true" => Ok(String::new()),
+            "false" => Err(String::new()),
+            "ls" => self.ls(args),
+            "mount" => self.mount(args),
+            "lsblk" => Ok(render_lsblk(&self.disks, &self.vfs, Options::parse("lsblk", args).has("fs"))),
+            "blkid" => Ok(render_blkid(&self.disks, &self.vfs)),
+            "fdisk" => self.fdisk(args, frontend),
+            "mkfs.fat" | "mkfs.vfat" => self.mkfs(args, "vfat"),
+            "mkfs.ext4" => self.mkfs(args, "ext4"),
+            "mkswap" => self.mkfs(args, "swap"),
+            "umount" => self.umount(args),
+            "pacstrap" => self.pacstrap(args),
+            "genfstab" => self.genfstab(args),
+            "arch-chroot" => self
+                .vfs
+                .chroot(args.first().copied().unwrap_or("/mnt"))
+                .map(|_| String::new())
+                .map_err(|err| format!("==> ERROR: Can't create chroot on non-directory: {}", err)),
+            "exit" => {
+                self.vfs.exit_chroot();
+                Ok(String::new())
+            }
+            "ln" => self.ln(args),
+            "sed" => self.sed(args),
+            "locale-gen" => self.write_file("/usr/lib/locale/locale-archive", "", false),
+            "hwclock" => self.write_file("/etc/adjtime", "0.000000 0 0.000000\n0\nUTC\n", false),
+            "useradd" => self.useradd(args),
+            "pacman" => self.install_packages("/", &Options::parse("pacman", args).operands()),
+            "grub-install" => self.grub_install(args),
+            "grub-mkconfig" => self.grub_mkconfig(args),
+            _ => Ok(String::new()),
+        }
+    }
+
+    /// Reads the named files, or standard input when there are none
+    fn input(&self, program: &str, paths: &[&str], stdin: &str) -> Result<String, String> {
+        if paths.is_empty() {
+            return Ok(stdin.to_string());
+        }
+        let mut out = String::new();
+        for path in paths {
+            let content = self.vfs.read(path).map_err(|err| format!("{}: {}: {}", program, path, err))?;
+            out.push_str(content);
+        }
+        Ok(out)
+    }
+
+    /// Supports fixed-string `grep [-i] [-v] PATTERN [FILE]...`
+    fn grep(&self, args: &[&str], stdin: &str) -> Result<String, String> {
+        let options = Options::parse("grep", args);
+        let Some((pattern, paths)) = options.operands().split_first().map(|(p, rest)| (p.to_string(), rest.to_vec())) else {
+            return Err("Usage: grep [OPTION]... PATTERNS [FILE]...".to_string());
+        };
+        let ignore_case = options.has("ignore-case");
+        let needle = if ignore_case { pattern.to_lowercase() } else { pattern };
+        let found: String = self
+            .input("grep", &paths, stdin)?
+            .lines()
+            .filter(|line| {
+                let hit = if ignore_case { line.to_lowercase().contains(&needle) } else { line.contains(&needle) };
+                hit != options.has("invert-match")
+            })
+            .map(|line| format!("{}\n", line))
+            .collect();
+        // grep exits with 1 when nothing matched
+        if found.is_empty() { Err(String::new()) } else { Ok(found) }
+    }
+
+    fn head_tail(&self, program: &str, args: &[&str], stdin: &str) -> Result<String, String> {
+        let options = Options::parse(program, args);
+        let count: usize = match options.value("lines") {
+            Some(value) => value.parse().map_err(|_| format!("{}: invalid number of lines: '{}'", program, value))?,
+            None => 10,
+        };
+        let text = self.input(program, &options.operands(), stdin)?;
+        let lines: Vec<&str> = text.lines().collect();
+        let kept = match program {
+            "head" => &lines[..count.min(lines.len())],
+            _ => &lines[lines.len().saturating_sub(count)..],
+        };
+        Ok(kept.iter().map(|line| format!("{}\n", line)).collect())
+    }
+
+    fn wc(&self, args: &[&str], stdin: &str) -> Result<String, String> {
+        let options = Options::parse("wc", args);
+        let text = self.input("wc", &options.operands(), stdin)?;
+        let lines = text.matches('\n').count();
+        if options.has("lines") {
+            return Ok(format!("{}\n", lines));
+        }
+        Ok(format!("{:>7} {:>7} {:>7}\n", lines, text.split_whitespace().count(), text.len()))
+    }
+
+    /// Copies standard input to the named files and to standard output
+    fn tee(&mut self, args: &[&str], stdin: &str) -> Result<String, String> {
+        let options = Options::parse("tee", args);
+        for path in options.operands() {
+            self.vfs
+                .write(path, stdin, options.has("append"))
+                .map_err(|err| format!("tee: {}: {}", path, err))?;
+        }
+        Ok(stdin.to_string())
+    }
+
+    fn export(&mut self, args: &[&str]) -> String {
+        if args.is_empty() {
+            return self.env.iter().map(|(name, value)| format!("declare -x {}=\"{}\"\n", name, value)).collect();
+        }
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) => self.env.insert(name.to_string(), value.to_string()),
+                None => self.env.insert(arg.to_string(), self.env.get(*arg).cloned().unwrap_or_default()),
+            };
+        }
+        String::new()
+    }
+
+    fn printenv(&self, args: &[&str]) -> Result<String, String> {
+        if args.is_empty() {
+            return Ok(self.env.iter().map(|(name, value)| format!("{}={}\n", name, value)).collect());
+        }
+        let values: Vec<&String> = args.iter().filter_map(|name| self.env.get(*name)).collect();
+        let out = values.iter().map(|value| format!("{}\n", value)).collect();
+        // printenv fails if any variable is unset
+        if values.len() == args.len() { Ok(out) } else { Err(out) }
+    }
+
+    fn cat(&self, args: &[&str]) -> Result<String, String> {
+        let mut out = String::new();
+        for path in args {
+            let content = self.vfs.read(path).map_err(|err| format!("cat: {}: {}", path, err))?;
+            out.push_str(content);
+        }
+        Ok(out)
+    }
+
+    fn ls(&self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("ls", args);
+        let long = options.has("-l");
+        let paths = match options.operands() {
+            paths if paths.is_empty() => vec!["."],
+            paths => paths,
+        };
+
+        let mut out = String::new();
+        for path in &paths {
+            if paths.len() > 1 {
+                out.push_str(&format!("{}:\n", path));
+            }
+            let entries = match self.vfs.list(path) {
+                Ok(entries) => entries,
+                Err(VfsError::NotADirectory) => vec![(path.to_string(), Node::File(String::new()))],
+                Err(err) => return Err(format!("ls: cannot access '{}': {}", path, err)),
+            };
+            for (name, node) in entries {
+                let line = match (&node, long) {
+                    (Node::Dir, true) => format!("drwxr-xr-x root root {}", name),
+                    (Node::File(data), true) => format!("-rw-r--r-- root root {:>5} {}", data.len(), name),
+                    (Node::Symlink(target), true) => format!("lrwxrwxrwx root root {} -> {}", name, target),
+                    _ => name,
+                };
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        Ok(out)
+    }
+
+    /// Supports `cd [DIR]`, going home without one
+    fn cd(&mut self, args: &[&str]) -> Result<String, String> {
+        let dir = match args {
+            [] => self.env.get("HOME").map_or("/root", String::as_str).to_string(),
+            [dir] => dir.to_string(),
+            _ => return Err("bash: cd: too many arguments".to_string()),
+        };
+        self.vfs.chdir(&dir).map(|_| String::new()).map_err(|err| format!("bash: cd: {}: {}", dir, err))
+    }
+
+    /// Supports `mkdir [-p] DIR...`
+    fn mkdir(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("mkdir", args);
+        let dirs = options.operands();
+        if dirs.is_empty() {
+            return Err("mkdir: missing operand".to_string());
+        }
+        for dir in dirs {
+            let made = if options.has("parents") { self.vfs.mkdir_p(dir) } else { self.vfs.mkdir(dir) };
+            made.map_err(|err| format!("mkdir: cannot create directory '{}': {}", dir, err))?;
+        }
+        Ok(String::new())
+    }
+
+    fn mount(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("mount", args);
+        match options.operands().as_slice() {
+            [] => Ok(self
+                .vfs
+                .mounts()
+                .iter()
+                .map(|m| {
+                    let fstype = self.vfs.filesystem(&m.source).map_or("none", |fs| fs.fstype.as_str());
+                    format!("{} on {} type {} (rw,relatime)\n", m.source, m.target, fstype)
+                })
+                .collect()),
+            [source, target] => {
+                if !self.block_device_exists(source) {
+                    return Err(format!("mount: {}: special device {} does not exist.", target, source));
+                }
+                if self.vfs.filesystem(source).is_none_or(|fs| fs.fstype == "swap") {
+                    return Err(format!(
+                        "mount: {}: wrong fs type, bad option, bad superblock on {}, missing codepage or helper program, or other error.",
+                        target, source
+                    ));
+                }
+                if options.has("mkdir") {
+                    let _ = self.vfs.mkdir_p(target);
+                }
+                self.vfs.mount(source, target).map(|_| String::new()).map_err(|err| match err {
+                    VfsError::NotFound => format!("mount: {}: mount point does not exist.", target),
+                    VfsError::Busy => format!("mount: {}: {} already mounted or mount point busy.", target, source),
+                    _ => format!("mount: {}: {} on {}.", target, err, source),
+                })
+            }
+            _ => Err("mount: bad usage\nTry 'mount --help' for more information.".to_string()),
+        }
+    }
+
+    fn fdisk(&mut self, args: &[&str], frontend: &mut dyn Frontend) -> Result<String, String> {
+        let options = Options::parse("fdisk", args);
+        let devices = options.operands();
+        if options.has("list") {
+            let disks: Vec<&Disk> = match devices.as_slice() {
+                [] => self.disks.iter().collect(),
+                paths => paths.iter().filter_map(|p| self.disk(p)).collect(),
+            };
+            return Ok(disks.into_iter().map(render_fdisk_list).collect::<Vec<_>>().join("\n"));
+        }
+        let [device] = devices.as_slice() else {
+            return Err("fdisk: bad usage\nTry 'fdisk --help' for more information.".to_string());
+        };
+        let Some(index) = self.disks.iter().position(|d| d.path() == *device) else {
+            return Err(format!("fdisk: cannot open {}: No such file or directory", device));
+        };
+
+        if let Outcome::Written(edited) = fdisk::run(&self.disks[index], frontend) {
+            // Filesystems only survive on partitions whose extents did not move
+            let old = std::mem::replace(&mut self.disks[index], edited);
+            for partition in &old.partitions {
+                let kept = self.disks[index].partition(partition.number).is_some_and(|p| p.start == partition.start && p.end == partition.end);
+                if !kept {
+                    self.vfs.wipe(&old.partition_path(partition.number));
+                }
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn mkfs(&mut self, args: &[&str], fstype: &str) -> Result<String, String> {
+        let program = match fstype {
+            "vfat" => "mkfs.fat",
+            "swap" => "mkswap",
+            _ => "mkfs.ext4",
+        };
+        let options = Options::parse(program, args);
+        let [device] = options.operands()[..] else {
+            return Err(format!("Usage: {} [OPTIONS] DEVICE", program));
+        };
+        if !self.block_device_exists(device) {
+            return Err(format!("{}: The file {} does not exist and no size was specified.", program, device));
+        }
+        if self.disk(device).is_some() {
+            return Err(format!("{}: Device partition expected, not making filesystem on entire device '{}' (use -I to override)", program, device));
+        }
+        if self.vfs.mounts().iter().any(|m| m.source == *device) {
+            return Err(format!("{}: {} contains a mounted filesystem.", program, device));
+        }
+
+        self.vfs.format(device, fstype);
+        let uuid = self.vfs.filesystem(device).map(|fs| fs.uuid.clone()).unwrap_or_default();
+        Ok(match fstype {
+            "vfat" => "mkfs.fat 4.2 (2021-01-31)\n".to_string(),
+            "swap" => format!("Setting up swapspace version 1\nno label, UUID={}\n", uuid),
+            _ => format!(
+                "mke2fs 1.47.1 (20-May-2024)\nDiscarding device blocks: done\nCreating filesystem with 4k blocks\nFilesystem UUID: {}\nAllocating group tables: done\nWriting inode tables: done\nCreating journal (65536 blocks): done\nWriting superblocks and filesystem accounting information: done\n",
+                uuid
+            ),
+        })
+    }
+
+    fn umount(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("umount", args);
+        let [target] = options.operands()[..] else {
+            return Err("umount: bad usage\nTry 'umount --help' for more information.".to_string());
+        };
+        self.vfs
+            .umount(target, options.has("recursive"))
+            .map(|_| String::new())
+            .map_err(|err| format!("umount: {}: {}.", target, err))
+    }
+
+    fn pacstrap(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("pacstrap", args);
+        let operands = options.operands();
+        let Some((root, packages)) = operands.split_first() else {
+            return Err("==> ERROR: No root directory specified".to_string());
+        };
+        if !self.vfs.is_dir(root) {
+            return Err(format!("==> ERROR: {} is not a directory", root));
+        }
+        self.install_packages(root, packages)
+    }
+
+    /// Unpacks packages below `root`, creating the base layout on first use
+    fn install_packages(&mut self, root: &str, packages: &[&str]) -> Result<String, String> {
+        let at = |path: &str| format!("{}{}", root.trim_end_matches('/'), path);
+
+        for dir in ["/boot", "/etc", "/home", "/root", "/tmp", "/usr/bin", "/usr/lib/locale", "/var/lib/pacman/local"] {
+            let _ = self.vfs.mkdir_p(&at(dir));
+        }
+        for package in packages {
+            let _ = self.vfs.mkdir_p(&at(&format!("/var/lib/pacman/local/{}", package)));
+            match *package {
+                "base" => {
+                    let _ = self.vfs.mkdir_p(&at("/usr/share/zoneinfo"));
+                    for zone in ZONEINFO {
+                        let path = at(&format!("/usr/share/zoneinfo/{}", zone));
+                        let _ = self.vfs.mkdir_p(&path[..path.rfind('/').unwrap_or(0)]);
+                        let _ = self.vfs.write(&path, "TZif2\n", false);
+                    }
+                    let locales: String = LOCALES.iter().map(|l| format!("#{}\n", l)).collect();
+                    let files = [
+                        ("/etc/fstab", "# Static information about the filesystems.\n# See fstab(5) for details.\n\n# <file system> <dir> <type> <options> <dump> <pass>\n".to_string()),
+                        ("/etc/hosts", "# Static table lookup for hostnames.\n# See hosts(5) for details.\n".to_string()),
+                        ("/etc/passwd", "root:x:0:0::/root:/bin/bash\n".to_string()),
+                        ("/etc/group", "root:x:0:root\nwheel:x:998:\n".to_string()),
+                        ("/etc/locale.gen", locales),
+                    ];
+                    for (path, content) in files {
+                        if !self.vfs.exists(&at(path)) {
+                            let _ = self.vfs.write(&at(path), &content, false);
+                        }
+                    }
+                }
+                "linux" => {
+                    let _ = self.vfs.write(&at("/boot/vmlinuz-linux"), "", false);
+                    let _ = self.vfs.write(&at("/boot/initramfs-linux.img"), "", false);
+                }
+                "amd-ucode" | "intel-ucode" => {
+                    let _ = self.vfs.write(&at(&format!("/boot/{}.img", package)), "", false);
+                }
+                _ => {}
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn genfstab(&self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("genfstab", args);
+        let Some(root) = options.operands.first() else {
+            return Err("==> ERROR: No root directory specified".to_string());
+        };
+        let root = root.trim_end_matches('/');
+        let mut out = String::new();
+        for mount in self.vfs.mounts() {
+            let Some(dir) = mount.target.strip_prefix(root) else { continue };
+            if !dir.is_empty() && !dir.starts_with('/') {
+                continue;
+            }
+            let Some(fs) = self.vfs.filesystem(&mount.source) else { continue };
+            let dir = if dir.is_empty() { "/" } else { dir };
+            let (options, pass) = match fs.fstype.as_str() {
+                "vfat" => ("rw,relatime,fmask=0022,dmask=0022,codepage=437,iocharset=ascii,shortname=mixed,utf8,errors=remount-ro", 2),
+                _ => ("rw,relatime", if dir == "/" { 1 } else { 2 }),
+            };
+            out.push_str(&format!(
+                "# {}\nUUID={:<36}\t{:<10}\t{:<10}\t{}\t0 {}\n\n",
+                mount.source, fs.uuid, dir, fs.fstype, options, pass
+            ));
+        }
+        Ok(out)
+    }
+
+    fn ln(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("ln", args);
+        let [target, link] = options.operands()[..] else {
+            return Err("ln: missing file operand".to_string());
+        };
+        self.vfs
+            .symlink(target, link)
+            .map(|_| String::new())
+            .map_err(|err| format!("ln: failed to create symbolic link '{}': {}", link, err))
+    }
+
+    /// Supports `sed -i 's/PATTERN/REPLACEMENT/[g]' FILE`
+    fn sed(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("sed", args);
+        let operands = options.operands();
+        let (script, path) = match (options.value("expression"), &operands[..]) {
+            (Some(script), [path]) => (script, *path),
+            (None, [script, path]) => (*script, *path),
+            _ => return Err("Usage: sed [OPTION]... {script-only-if-no-other-script} [input-file]...".to_string()),
+        };
+        let parts: Vec<&str> = script.strip_prefix('s').unwrap_or("").split('/').collect();
+        let [_, pattern, replacement, flags] = parts.as_slice() else {
+            return Err(format!("sed: -e expression #1, char {}: unterminated `s' command", script.len()));
+        };
+        let content = self.vfs.read(path).map_err(|err| format!("sed: can't read {}: {}", path, err))?;
+        let edited: String = content
+            .lines()
+            .map(|line| format!("{}\n", substitute(line, pattern, replacement, flags.contains('g'))))
+            .collect();
+        if options.has("in-place") {
+            self.write_file(path, &edited, false)
+        } else {
+            Ok(edited)
+        }
+    }
+
+    fn useradd(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("useradd", args);
+        let groups: Vec<&str> = options.value("groups").map(|g| g.split(',').collect()).unwrap_or_default();
+        let [name] = options.operands()[..] else {
+            return Err("Usage: useradd [options] LOGIN".to_string());
+        };
+
+        let passwd = self.vfs.read("/etc/passwd").unwrap_or("").to_string();
+        if passwd.lines().any(|line| line.split(':').next() == Some(name)) {
+            return Err(format!("useradd: user '{}' already exists", name));
+        }
+        let uid = 1000 + passwd.lines().filter(|l| l.contains(":/home/")).count();
+        self.write_file("/etc/passwd", &format!("{0}:x:{1}:{1}::/home/{0}:/bin/bash\n", name, uid), true)?;
+
+        if let Ok(group) = self.vfs.read("/etc/group") {
+            let updated: String = group
+                .lines()
+                .map(|line| {
+                    let members_empty = line.ends_with(':');
+                    match line.split(':').next() {
+                        Some(g) if groups.contains(&g) => {
+                            format!("{}{}{}\n", line, if members_empty { "" } else { "," }, name)
+                        }
+                        _ => format!("{}\n", line),
+                    }
+                })
+                .collect();
+            self.write_file("/etc/group", &updated, false)?;
+        }
+        if options.has("create-home") {
+            let _ = self.vfs.mkdir_p(&format!("/home/{}", name));
+        }
+        Ok(String::new())
+    }
+
+    fn grub_install(&mut self, args: &[&str]) -> Result<String, String> {
+        let options = Options::parse("grub-install", args);
+        let efi_dir = options.value("efi-directory").unwrap_or("/boot/efi");
+        let id = options.value("bootloader-id").unwrap_or("arch");
+        if !self.vfs.is_dir(efi_dir) {
+            return Err(format!("grub-install: error: {} doesn't look like an EFI partition.", efi_dir));
+        }
+        let _ = self.vfs.mkdir_p(&format!("{}/EFI/{}", efi_dir, id));
+        self.write_file(&format!("{}/EFI/{}/grubx64.efi", efi_dir, id), "", false)?;
+        let _ = self.vfs.mkdir_p("/boot/grub/x86_64-efi");
+        Ok(String::new())
+    }
+
+    fn grub_mkconfig(&mut self, args: &[&str]) -> Result<String, String> {
+        let root_uuid = self
+            .vfs
+            .mounts()
+            .iter()
+            .find(|m| m.target == self.vfs.root())
+            .and_then(|m| self.vfs.filesystem(&m.source))
+            .map(|fs| fs.uuid.clone())
+            .unwrap_or_default();
+        let ucode: Vec<String> = ["amd-ucode", "intel-ucode"]
+            .iter()
+            .filter(|u| self.vfs.exists(&format!("/boot/{}.img", u)))
+            .map(|u| format!("/boot/{}.img", u))
+            .collect();
+        let initrd = ucode.into_iter().chain(["/boot/initramfs-linux.img".to_string()]).collect::<Vec<_>>().join(" ");
+        let config = format!(
+            "### BEGIN /etc/grub.d/10_linux ###\nmenuentry 'Arch Linux' {{\n\tlinux\t/boot/vmlinuz-linux root=UUID={} rw loglevel=3 quiet\n\tinitrd\t{}\n}}\n### END /etc/grub.d/10_linux ###\n",
+            root_uuid, initrd
+        );
+
+        let options = Options::parse("grub-mkconfig", args);
+        match (options.has("output"), options.value("output")) {
+            (true, None) => Err("grub-mkconfig: option requires an argument -- 'o'".to_string()),
+            (_, Some(path)) => self.write_file(path, &config, false),
+            (false, None) => Ok(config),
+        }
+    }
+
+    fn write_file(&mut self, path: &str, content: &str, append: bool) -> Result<String, String> {
+        self.vfs
+            .write(path, content, append)
+            .map(|_| String::new())
+            .map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+/// Applies a basic-regex substitution supporting `^`, `$` and `.`
+fn substitute(line: &str, pattern: &str, replacement: &str, global: bool) -> String {
+    let anchored_start = pattern.starts_with('^');
+    let anchored_end = pattern.ends_with('$') && !pattern.ends_with("\\$");
+    let body = pattern.trim_start_matches('^');
+    let body = if anchored_end { &body[..body.len() - 1] } else { body };
+    let atoms = parse_atoms(body);
+    let chars: Vec<char> = line.chars().collect();
+
+    let mut out = String::new();
+    let mut index = 0;
+    let mut replaced = false;
+    while index <= chars.len() {
+        let allowed = (!anchored_start || index == 0) && (global || !replaced);
+        let matched = allowed
+            .then(|| match_at(&atoms, &chars[index..]))
+            .flatten()
+            .filter(|len| !anchored_end || index + len == chars.len());
+        match matched {
+            Some(len) => {
+                out.push_str(replacement);
+                replaced = true;
+                if len == 0 {
+                    out.extend(chars.get(index));
+                    index += 1;
+                } else {
+                    index += len;
+                }
+            }
+            None => {
+                out.extend(chars.get(index));
+                index += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Pattern element: `None` matches any character
+fn parse_atoms(pattern: &str) -> Vec<Option<char>> {
+    let mut atoms = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        atoms.push(match c {
+            '.' => None,
+            '\\' => chars.next(),
+            c => Some(c),
+        });
+    }
+    atoms
+}
+
+/// Returns the match length if the atoms match at the start of `text`
+fn match_at(atoms: &[Option<char>], text: &[char]) -> Option<usize> {
+    if atoms.len() > text.len() {
+        return None;
+    }
+    atoms
+        .iter()
+        .zip(text)
+        .all(|(atom, c)| atom.is_none_or(|a| a == *c))
+        .then_some(atoms.len())
+}
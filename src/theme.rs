@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use colored::{Color, ColoredString, Colorize};
+use serde::Deserialize;
+
+/// Colour presets for lesson output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Bright colours for dark terminals
+    #[default]
+    Default,
+    /// Darker colours that stay readable on a white background
+    Light,
+    /// No colours at all
+    Mono,
+}
+
+/// What a piece of text is, which decides its colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Stage headers, step descriptions and links
+    Header,
+    /// Success banners and good news
+    Success,
+    /// Warnings, notices and step guides
+    Warning,
+    /// The command a hint suggests
+    Hint,
+    /// Errors and rejected commands
+    Error,
+    /// The user name in the shell prompt
+    User,
+    /// Asides that matter less than the text around them
+    Muted,
+}
+
+impl Role {
+    const ALL: [Role; 7] = [Role::Header, Role::Success, Role::Warning, Role::Hint, Role::Error, Role::User, Role::Muted];
+
+    /// Key used for this role in the `[colors]` table of the config file
+    fn name(self) -> &'static str {
+        match self {
+            Role::Header => "header",
+            Role::Success => "success",
+            Role::Warning => "warning",
+            Role::Hint => "hint",
+            Role::Error => "error",
+            Role::User => "user",
+            Role::Muted => "muted",
+        }
+    }
+}
+
+impl Theme {
+    fn color(self, role: Role) -> Option<Color> {
+        match self {
+            Theme::Default => Some(match role {
+                Role::Header => Color::BrightBlue,
+                Role::Success => Color::BrightGreen,
+                Role::Warning => Color::BrightYellow,
+                Role::Hint => Color::BrightCyan,
+                Role::Error => Color::Red,
+                Role::User => Color::BrightRed,
+                Role::Muted => Color::BrightBlack,
+            }),
+            Theme::Light => Some(match role {
+                Role::Header => Color::Blue,
+                Role::Success => Color::Green,
+                Role::Warning => Color::Magenta,
+                Role::Hint => Color::Cyan,
+                Role::Error => Color::Red,
+                Role::User => Color::Red,
+                Role::Muted => Color::BrightBlack,
+            }),
+            Theme::Mono => None,
+        }
+    }
+}
+
+/// Colour of each role, indexed by `Role as usize`, once a theme is applied
+static PALETTE: RwLock<Option<[Option<Color>; 7]>> = RwLock::new(None);
+
+/// Uses `theme` for all further output, with per-role `overrides` such as
+/// `header = "magenta"`. Unknown roles or colours are reported and ignored.
+pub fn apply(theme: Theme, overrides: &BTreeMap<String, String>) -> Result<(), String> {
+    let mut palette = Role::ALL.map(|role| theme.color(role));
+    let mut problems = Vec::new();
+    for (name, color) in overrides {
+        match (Role::ALL.iter().find(|role| role.name() == name), color.parse::<Color>()) {
+            (Some(role), Ok(color)) => {
+                palette[*role as usize] = Some(color);
+            }
+            (None, _) => problems.push(format!("unknown colour role '{}'", name)),
+            (_, Err(())) => problems.push(format!("unknown colour '{}' for {}", color, name)),
+        }
+    }
+    *PALETTE.write().unwrap_or_else(|e| e.into_inner()) = Some(palette);
+    if problems.is_empty() { Ok(()) } else { Err(problems.join(", ")) }
+}
+
+fn color(role: Role) -> Option<Color> {
+    match PALETTE.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(palette) => palette[role as usize],
+        None => Theme::Default.color(role),
+    }
+}
+
+/// Colours text by what it is, following the current theme
+pub trait Themed {
+    fn themed(&self, role: Role) -> ColoredString;
+}
+
+impl Themed for str {
+    fn themed(&self, role: Role) -> ColoredString {
+        match color(role) {
+            Some(color) => self.color(color),
+            None => self.normal(),
+        }
+    }
+}
@@ -1,17 +1,17 @@
-use colored::Colorize;
+use crate::theme::{Role, Themed};
+use crate::frontend::Frontend;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::{thread, time::Duration};
-
-/// Pauses execution for specified seconds
-pub fn sleep(secs: u64) {
-    thread::sleep(Duration::from_secs(secs));
-}
 
 /// Clears terminal screen
 pub fn clear_screen() {
     print!("{}[2J", 27 as char);
 }
 
+/// Byte offset just past the last character matching `pred`, or 0
+pub fn after_last(text: &str, pred: impl Fn(char) -> bool) -> usize {
+    text.char_indices().rfind(|(_, c)| pred(*c)).map_or(0, |(i, c)| i + c.len_utf8())
+}
+
 /// Creates progress bar with custom style
 pub fn create_progress_bar(length: u64) -> ProgressBar {
     let pb = ProgressBar::new(length);
@@ -25,18 +25,18 @@ pub fn create_progress_bar(length: u64) -> ProgressBar {
 }
 
 /// Displays colored header message
-pub fn show_header(message: &str) {
-    println!("\n{}", message.bright_blue());
-    sleep(1);
+pub fn show_header(frontend: &mut dyn Frontend, message: &str) {
+    frontend.println(&format!("\n{}", message.themed(Role::Header)));
+    frontend.pause(1);
 }
 
 /// Displays colored success message
-pub fn show_success(message: &str) {
-    println!("\n{}\n", message.bright_green());
-    sleep(1);
+pub fn show_success(frontend: &mut dyn Frontend, message: &str) {
+    frontend.println(&format!("\n{}\n", message.themed(Role::Success)));
+    frontend.pause(1);
 }
 
 /// Displays colored warning message
-pub fn show_warning(message: &str) {
-    println!("{}", message.bright_yellow());
+pub fn show_warning(frontend: &mut dyn Frontend, message: &str) {
+    frontend.println(&message.themed(Role::Warning).to_string());
 }
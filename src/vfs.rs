@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Name of the live ISO filesystem mounted at `/`
+pub const LIVE_FS: &str = "airootfs";
+
+/// Maximum symlink hops before giving up, like ELOOP
+const MAX_LINK_DEPTH: usize = 8;
+
+/// Entry in a simulated filesystem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Node {
+    Dir,
+    File(String),
+    Symlink(String),
+}
+
+/// Errors mirroring the errno values real tools report
+#[derive(Debug, PartialEq)]
+pub enum VfsError {
+    NotFound,
+    Exists,
+    NotADirectory,
+    IsADirectory,
+    TooManyLinks,
+    NoFilesystem,
+    Busy,
+    NotMounted,
+}
+
+impl fmt::Display for VfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VfsError::NotFound => write!(f, "No such file or directory"),
+            VfsError::Exists => write!(f, "File exists"),
+            VfsError::NotADirectory => write!(f, "Not a directory"),
+            VfsError::IsADirectory => write!(f, "Is a directory"),
+            VfsError::TooManyLinks => write!(f, "Too many levels of symbolic links"),
+            VfsError::NoFilesystem => write!(f, "wrong fs type, bad option, bad superblock"),
+            VfsError::Busy => write!(f, "target is busy"),
+            VfsError::NotMounted => write!(f, "not mounted"),
+        }
+    }
+}
+
+/// A formatted block device: a tree of nodes keyed by path relative to its root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Filesystem {
+    pub fstype: String,
+    pub uuid: String,
+    nodes: BTreeMap<String, Node>,
+}
+
+impl Filesystem {
+    /// Creates an empty filesystem with a UUID derived from the device name
+    pub fn new(device: &str, fstype: &str) -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(String::new(), Node::Dir);
+        Filesystem {
+            fstype: fstype.to_string(),
+            uuid: fake_uuid(device, fstype),
+            nodes,
+        }
+    }
+}
+
+/// Filesystem attached to a directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mount {
+    pub source: String,
+    pub target: String,
+}
+
+/// In-memory view of every mounted filesystem, as seen from the current root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vfs {
+    filesystems: BTreeMap<String, Filesystem>,
+    mounts: Vec<Mount>,
+    root: String,
+    /// Working directory as seen from the current root, which relative paths start from
+    #[serde(default = "home")]
+    cwd: String,
+    /// The live environment's working directory, kept while in a chroot
+    #[serde(default = "home")]
+    outer_cwd: String,
+}
+
+impl Vfs {
+    /// Creates the live environment with only the ISO root mounted
+    pub fn new() -> Self {
+        let mut vfs = Vfs {
+            filesystems: BTreeMap::new(),
+            mounts: Vec::new(),
+            root: "/".to_string(),
+            cwd: home(),
+            outer_cwd: home(),
+        };
+        vfs.filesystems.insert(LIVE_FS.to_string(), Filesystem::new(LIVE_FS, "airootfs"));
+        vfs.mounts.push(Mount { source: LIVE_FS.to_string(), target: "/".to_string() });
+        vfs
+    }
+
+    /// Creates a fresh filesystem on a device, discarding previous contents
+    pub fn format(&mut self, device: &str, fstype: &str) {
+        self.filesystems.insert(device.to_string(), Filesystem::new(device, fstype));
+    }
+
+    /// Forgets the filesystem stored on a device
+    pub fn wipe(&mut self, device: &str) {
+        self.filesystems.remove(device);
+    }
+
+    /// Returns the filesystem stored on a device
+    pub fn filesystem(&self, device: &str) -> Option<&Filesystem> {
+        self.filesystems.get(device)
+    }
+
+    /// Returns active mounts in mount order
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// Returns the absolute path currently acting as `/`
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Changes the apparent root directory
+    pub fn chroot(&mut self, path: &str) -> Result<(), VfsError> {
+        if !self.is_dir(path) {
+            return Err(VfsError::NotFound);
+        }
+        self.root = self.resolve(path);
+        self.outer_cwd = std::mem::replace(&mut self.cwd, "/".to_string());
+        Ok(())
+    }
+
+    /// Leaves the chroot, returning to the live environment
+    pub fn exit_chroot(&mut self) {
+        if self.root != "/" {
+            self.root = "/".to_string();
+            self.cwd = std::mem::replace(&mut self.outer_cwd, home());
+        }
+    }
+
+    /// Returns the working directory as seen from the current root
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    /// Changes the working directory
+    pub fn chdir(&mut self, path: &str) -> Result<(), VfsError> {
+        match self.stat(path)? {
+            Node::Dir => {
+                self.cwd = normalize(&self.absolute(path));
+                Ok(())
+            }
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+
+    /// Attaches a device's filesystem to a directory
+    pub fn mount(&mut self, source: &str, target: &str) -> Result<(), VfsError> {
+        if !self.filesystems.contains_key(source) {
+            return Err(VfsError::NoFilesystem);
+        }
+        if !self.is_dir(target) {
+            return Err(VfsError::NotFound);
+        }
+        let target = self.resolve(target);
+        if self.mounts.iter().any(|m| m.source == source || m.target == target) {
+            return Err(VfsError::Busy);
+        }
+        self.mounts.push(Mount { source: source.to_string(), target });
+        Ok(())
+    }
+
+    /// Detaches a mount and, with `recursive`, everything below it
+    pub fn umount(&mut self, target: &str, recursive: bool) -> Result<(), VfsError> {
+        let target = self.resolve(target);
+        if !self.mounts.iter().any(|m| m.target == target) || target == "/" {
+            return Err(VfsError::NotMounted);
+        }
+        let below = |m: &Mount| is_below(&m.target, &target) && m.target != target;
+        if !recursive && self.mounts.iter().any(below) {
+            return Err(VfsError::Busy);
+        }
+        self.mounts.retain(|m| m.target != target && !below(m));
+        Ok(())
+    }
+
+    /// Returns the node at a path, following symlinks
+    pub fn stat(&self, path: &str) -> Result<&Node, VfsError> {
+        let resolved = self.follow(&self.resolve(path), 0)?;
+        self.node(&resolved).ok_or(VfsError::NotFound)
+    }
+
+    /// Returns true if the path exists
+    pub fn exists(&self, path: &str) -> bool {
+        self.stat(path).is_ok()
+    }
+
+    /// Returns true if the path is a directory
+    pub fn is_dir(&self, path: &str) -> bool {
+        matches!(self.stat(path), Ok(Node::Dir))
+    }
+
+    /// Reads a file's contents
+    pub fn read(&self, path: &str) -> Result<&str, VfsError> {
+        match self.stat(path)? {
+            Node::File(content) => Ok(content),
+            Node::Dir => Err(VfsError::IsADirectory),
+            Node::Symlink(_) => Err(VfsError::TooManyLinks),
+        }
+    }
+
+    /// Lists a directory as `(name, node)` pairs sorted by name
+    pub fn list(&self, path: &str) -> Result<Vec<(String, Node)>, VfsError> {
+        let dir = self.follow(&self.resolve(path), 0)?;
+        match self.node(&dir) {
+            Some(Node::Dir) => {}
+            Some(_) => return Err(VfsError::NotADirectory),
+            None => return Err(VfsError::NotFound),
+        }
+        let (fs, rel) = self.locate(&dir);
+        let prefix = if rel.is_empty() { String::new() } else { format!("{}/", rel) };
+        let entries = self.filesystems[fs]
+            .nodes
+            .iter()
+            .filter_map(|(key, node)| {
+                let name = key.strip_prefix(&prefix)?;
+                (!name.is_empty() && !name.contains('/')).then(|| (name.to_string(), node.clone()))
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Creates a directory whose parent exists
+    pub fn mkdir(&mut self, path: &str) -> Result<(), VfsError> {
+        let full = self.resolve(path);
+        if self.exists(path) {
+            return Err(VfsError::Exists);
+        }
+        match self.node(parent(&full)) {
+            Some(Node::Dir) => {
+                self.insert(&full, Node::Dir);
+                Ok(())
+            }
+            Some(_) => Err(VfsError::NotADirectory),
+            None => Err(VfsError::NotFound),
+        }
+    }
+
+    /// Creates a directory and any missing parents
+    pub fn mkdir_p(&mut self, path: &str) -> Result<(), VfsError> {
+        let full = self.resolve(path);
+        let mut current = String::new();
+        for part in full.split('/').filter(|p| !p.is_empty()) {
+            current = format!("{}/{}", current, part);
+            match self.node(&current) {
+                Some(Node::Dir) => continue,
+                Some(_) => return Err(VfsError::NotADirectory),
+                None => self.insert(&current, Node::Dir),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a file, replacing it or appending to it
+    pub fn write(&mut self, path: &str, content: &str, append: bool) -> Result<(), VfsError> {
+        let full = self.follow(&self.resolve(path), 0).unwrap_or_else(|_| self.resolve(path));
+        if !matches!(self.node(parent(&full)), Some(Node::Dir)) {
+            return Err(VfsError::NotFound);
+        }
+        let mut data = match self.node(&full) {
+            Some(Node::Dir) => return Err(VfsError::IsADirectory),
+            Some(Node::File(existing)) if append => existing.clone(),
+            _ => String::new(),
+        };
+        data.push_str(content);
+        self.insert(&full, Node::File(data));
+        Ok(())
+    }
+
+    /// Creates or replaces a symbolic link
+    pub fn symlink(&mut self, target: &str, link: &str) -> Result<(), VfsError> {
+        let full = self.resolve(link);
+        if !matches!(self.node(parent(&full)), Some(Node::Dir)) {
+            return Err(VfsError::NotFound);
+        }
+        self.insert(&full, Node::Symlink(target.to_string()));
+        Ok(())
+    }
+
+    /// Makes a user path absolute, starting relative ones at the working directory
+    fn absolute(&self, path: &str) -> String {
+        if path.starts_with('/') { path.to_string() } else { format!("{}/{}", self.cwd, path) }
+    }
+
+    /// Maps a user path onto an absolute path outside any chroot
+    fn resolve(&self, path: &str) -> String {
+        let inner = normalize(&self.absolute(path));
+        if self.root == "/" {
+            inner
+        } else if inner == "/" {
+            self.root.clone()
+        } else {
+            format!("{}{}", self.root, inner)
+        }
+    }
+
+    /// Follows symlinks in every component of an absolute path
+    fn follow(&self, path: &str, depth: usize) -> Result<String, VfsError> {
+        if depth > MAX_LINK_DEPTH {
+            return Err(VfsError::TooManyLinks);
+        }
+        let mut current = String::new();
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        for (index, part) in parts.iter().enumerate() {
+            current = format!("{}/{}", current, part);
+            match self.node(&current) {
+                Some(Node::Symlink(target)) => {
+                    let base = if target.starts_with('/') {
+                        self.resolve(target)
+                    } else {
+                        normalize(&format!("{}/{}", parent(&current), target))
+                    };
+                    let rest = parts[index + 1..].join("/");
+                    return self.follow(&format!("{}/{}", base, rest), depth + 1);
+                }
+                Some(Node::File(_)) if index + 1 < parts.len() => return Err(VfsError::NotADirectory),
+                Some(_) => {}
+                None => return Ok(normalize(path)),
+            }
+        }
+        Ok(if current.is_empty() { "/".to_string() } else { current })
+    }
+
+    /// Finds the filesystem and relative path backing an absolute path
+    fn locate(&self, path: &str) -> (&str, String) {
+        let mount = self
+            .mounts
+            .iter()
+            .filter(|m| is_below(path, &m.target))
+            .max_by_key(|m| m.target.len())
+            .expect("root is always mounted");
+        let rel = path[mount.target.len()..].trim_start_matches('/').to_string();
+        (&mount.source, rel)
+    }
+
+    /// Looks up a node without following symlinks
+    fn node(&self, path: &str) -> Option<&Node> {
+        let (fs, rel) = self.locate(path);
+        self.filesystems.get(fs)?.nodes.get(&rel)
+    }
+
+    /// Stores a node in whichever filesystem backs the path
+    fn insert(&mut self, path: &str, node: Node) {
+        let (fs, rel) = self.locate(path);
+        let fs = fs.to_string();
+        if let Some(filesystem) = self.filesystems.get_mut(&fs) {
+            filesystem.nodes.insert(rel, node);
+        }
+    }
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Vfs::new()
+    }
+}
+
+/// Collapses `.`, `..` and repeated slashes into an absolute path
+pub fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Where the live environment's root shell starts
+fn home() -> String {
+    "/root".to_string()
+}
+
+/// Returns the parent directory of an absolute path
+fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &path[..index],
+    }
+}
+
+/// Returns true if `path` is `dir` or lies inside it
+fn is_below(path: &str, dir: &str) -> bool {
+    dir == "/" || path == dir || path.starts_with(&format!("{}/", dir))
+}
+
+/// Derives a stable UUID so fstab output is reproducible between runs
+pub fn fake_uuid(device: &str, fstype: &str) -> String {
+    let hash = device
+        .bytes()
+        .chain(fstype.bytes())
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    if fstype == "vfat" {
+        let short = (hash >> 32) as u32;
+        format!("{:04X}-{:04X}", short >> 16, short & 0xffff)
+    } else {
+        let low = hash.rotate_left(17) ^ 0x9e37_79b9_7f4a_7c15;
+        format!(
+            "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+            (hash >> 32) as u32,
+            (hash >> 16) as u16,
+            hash & 0xfff,
+            (low >> 48) as u16 & 0x3fff | 0x8000,
+            low & 0xffff_ffff_ffff
+        )
+    }
+}
@@ -0,0 +1,39 @@
+use archtry::config::{get_user_choices, Presets};
+use archtry::frontend::Memory;
+use archtry::models::Mode;
+use archtry::scenario::Scenario;
+use archtry::simulator::{run_scenario, Options};
+
+/// A lesson that partitions and formats the disk out of sight, runs `setup`,
+/// then has a single step expecting `expected` under `rules`
+pub fn lesson(setup: &[&str], expected: &str, rules: &str) -> Scenario {
+    let mut toml = String::from(
+        "name = \"matching\"\n\n[[stages]]\nid = \"setup\"\n\n\
+         [[stages.steps]]\nkind = \"command\"\ncommand = \"fdisk /dev/sdz\"\ndescription = \"Partition\"\n\
+         answers = [\"g\", \"n\", \"\", \"\", \"+1G\", \"n\", \"\", \"\", \"\", \"w\"]\n",
+    );
+    for command in ["mkfs.fat -F 32 /dev/sdz1", "mkfs.ext4 /dev/sdz2"].iter().chain(setup) {
+        toml += &format!("\n[[stages.steps]]\nkind = \"command\"\ncommand = '''{}'''\ndescription = \"Set up\"\n", command);
+    }
+    toml += &format!(
+        "\n[[stages]]\nid = \"test\"\nchapter = \"Test\"\n\n[[stages.steps]]\nkind = \"command\"\ncommand = '''{}'''\ndescription = \"Type the command\"\n",
+        expected
+    );
+    if !rules.is_empty() {
+        toml += &format!("\n[stages.steps.rules]\n{}\n", rules);
+    }
+    Scenario::parse(&toml).unwrap_or_else(|err| panic!("{}\n{}", err, toml))
+}
+
+/// Returns true if the step accepts `typed` as its first answer in `mode`
+pub fn accepts_in(mode: Mode, scenario: &Scenario, typed: &str) -> bool {
+    colored::control::set_override(false);
+    let mut frontend = Memory::new(["", "", typed]);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let options = Options { mode, chapter: Some("test".to_string()), ..Options::default() };
+    // A rejected line leaves the step waiting for input that never comes
+    let flow = run_scenario(scenario, &choices, &options, &mut frontend);
+    assert_eq!(frontend.remaining(), 0, "{}", frontend.output());
+    flow.is_continue()
+}
+
@@ -0,0 +1,109 @@
+mod common;
+
+use archtry::input_handler::{edit, Completer};
+use archtry::models::Mode;
+use archtry::scenario::Scenario;
+use common::{accepts_in, lesson};
+
+/// Exam mode never offers the expected command, so only the machine completes
+fn completes(scenario: &Scenario, typed: &str) -> bool {
+    accepts_in(Mode::Exam, scenario, typed)
+}
+
+#[test]
+fn program_names_complete() {
+    let scenario = lesson(&[], "lsblk", "");
+    assert!(completes(&scenario, "lsb\t"));
+    assert!(completes(&scenario, "lsblk -f; ls\tb\t"));
+    assert!(!completes(&scenario, "ls\t"));
+}
+
+#[test]
+fn flags_complete_for_known_programs() {
+    let scenario = lesson(&["mount /dev/sdz2 /mnt"], "mount --mkdir /dev/sdz1 /mnt/boot", "");
+    assert!(completes(&scenario, "mount --mk\t/dev/sdz1 /mnt/boot"));
+    let scenario = lesson(&[], "mkfs.fat -F 32 /dev/sdz1", "");
+    assert!(completes(&scenario, "mkfs.fat --fat\t32 /dev/sdz1"));
+}
+
+#[test]
+fn devices_complete_to_their_shared_prefix() {
+    let scenario = lesson(&[], "mkfs.ext4 /dev/sdz2", "");
+    assert!(completes(&scenario, "mkfs.ext4 /dev/s\t2"));
+    assert!(completes(&scenario, "mkfs.ext4 /dev/sdz\t\t2"));
+}
+
+#[test]
+fn paths_complete_from_the_simulated_machine() {
+    let scenario = lesson(&["mount /dev/sdz2 /mnt", "mount --mkdir /dev/sdz1 /mnt/boot"], "umount /mnt/boot", "");
+    assert!(completes(&scenario, "umount /mn\tb\t"));
+    let scenario = lesson(&["mount /dev/sdz2 /mnt", "pacstrap -K /mnt base"], "genfstab -U /mnt >> /mnt/etc/fstab", "");
+    assert!(completes(&scenario, "genfstab -U /mnt >> /mnt/e\tfstab"));
+}
+
+#[test]
+fn packages_complete_after_pacstrap_and_pacman() {
+    let scenario = lesson(&["mount /dev/sdz2 /mnt"], "pacstrap -K /mnt base linux-firmware", "");
+    assert!(completes(&scenario, "pacstrap -K /mnt bas\t linux-fi\t"));
+    let scenario = lesson(&[], "pacman -S efibootmgr", "");
+    assert!(completes(&scenario, "pacman -S efib\t"));
+}
+
+#[test]
+fn the_expected_command_completes_only_once_shown() {
+    let scenario = lesson(&[], "echo archlinux > /etc/hostname", "");
+    assert!(accepts_in(Mode::Guided, &scenario, "echo arch\t> /etc/hostname"));
+    assert!(!accepts_in(Mode::Practice, &scenario, "echo arch\t> /etc/hostname"));
+    assert!(!accepts_in(Mode::Exam, &scenario, "echo arch\t> /etc/hostname"));
+}
+
+/// Offers the same candidates whatever is typed
+struct Fixed(&'static [&'static str]);
+
+impl Completer for Fixed {
+    fn complete(&self, line: &str, cursor: usize) -> (usize, Vec<String>) {
+        let start = line[..cursor].rfind(' ').map_or(0, |index| index + 1);
+        let word = &line[start..cursor];
+        (start, self.0.iter().filter(|c| c.starts_with(word)).map(|c| c.to_string()).collect())
+    }
+}
+
+/// Presses `keys` and Enter at a `# ` prompt, returning the line and what was drawn
+fn tab(completer: &Fixed, keys: &str) -> (String, String) {
+    let mut drawn = Vec::new();
+    let keys = format!("{}\r", keys);
+    let line = edit(&mut keys.as_bytes(), &mut drawn, "# ", &[], Some(completer)).unwrap();
+    (line, String::from_utf8(drawn).unwrap())
+}
+
+#[test]
+fn tab_inserts_the_prefix_candidates_share() {
+    let packages = Fixed(&["base", "base-devel", "linux", "linux-firmware", "linux-lts"]);
+    assert_eq!(tab(&packages, "pacstrap ba\t").0, "pacstrap base");
+    assert_eq!(tab(&packages, "pacstrap linux-f\t").0, "pacstrap linux-firmware ");
+    assert_eq!(tab(&packages, "pacstrap vim\t").0, "pacstrap vim");
+
+    let accents = Fixed(&["caf\u{e9}-cr\u{e8}me", "caf\u{e9}-au-lait"]);
+    assert_eq!(tab(&accents, "c\t").0, "caf\u{e9}-");
+}
+
+#[test]
+fn directories_stay_open_for_the_next_tab() {
+    let paths = Fixed(&["/mnt/", "/mnt/boot/"]);
+    assert_eq!(tab(&paths, "ls /mnt/b\t").0, "ls /mnt/boot/");
+}
+
+#[test]
+fn a_second_tab_lists_the_candidates() {
+    let packages = Fixed(&["linux", "linux-firmware", "linux-lts"]);
+    let (line, drawn) = tab(&packages, "pacstrap linux\t");
+    assert_eq!(line, "pacstrap linux");
+    assert!(!drawn.contains("linux-lts"), "{:?}", drawn);
+
+    let (_, drawn) = tab(&packages, "pacstrap linux\t\t");
+    assert!(drawn.contains("\nlinux           linux-firmware  linux-lts\n# "), "{:?}", drawn);
+
+    let paths = Fixed(&["/mnt/boot/", "/mnt/etc/"]);
+    let (_, drawn) = tab(&paths, "ls /mnt/\t\t");
+    assert!(drawn.contains("\nboot/  etc/\n# "), "{:?}", drawn);
+}
@@ -0,0 +1,93 @@
+use archtry::config::{get_user_choices, Presets};
+use archtry::frontend::Memory;
+use archtry::scenario::Scenario;
+use archtry::simulator::{run_scenario, Options};
+
+/// The EFI and root partitions of the bundled lesson, written to disk
+const GPT_LAYOUT: &[&str] = &["g", "n", "", "", "+1G", "n", "", "", "", "w"];
+
+/// Runs an `fdisk /dev/sdz` session per entry of `sessions`, then returns
+/// everything printed and the partition table `fdisk -l` shows afterwards
+fn fdisk(sessions: &[&[&str]]) -> (String, String) {
+    let mut toml = String::from("name = \"fdisk\"\n\n[[stages]]\nid = \"disk\"\n");
+    let mut inputs = vec!["", ""];
+    for session in sessions {
+        toml += "\n[[stages.steps]]\nkind = \"command\"\ncommand = \"fdisk /dev/sdz\"\ndescription = \"Partition\"\n";
+        inputs.push("fdisk /dev/sdz");
+        inputs.extend(*session);
+    }
+    toml += "\n[[stages.steps]]\nkind = \"command\"\ncommand = \"fdisk -l /dev/sdz\"\ndescription = \"List\"\n";
+    inputs.push("fdisk -l /dev/sdz");
+
+    colored::control::set_override(false);
+    let scenario = Scenario::parse(&toml).unwrap();
+    let mut frontend = Memory::new(inputs);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let flow = run_scenario(&scenario, &choices, &Options::default(), &mut frontend);
+    let output = frontend.output().to_string();
+    assert!(flow.is_continue(), "{}", output);
+    assert_eq!(frontend.remaining(), 0, "{}", output);
+    let listed = output.rsplit_once("# fdisk -l /dev/sdz\n").unwrap().1;
+    let table = listed[..listed.find("Report card").unwrap()].trim_end().to_string();
+    (output, table)
+}
+
+/// The columns of `device`'s row in `table`, if it has one
+fn row<'a>(table: &'a str, device: &str) -> Option<Vec<&'a str>> {
+    table.lines().map(|line| line.split_whitespace().collect::<Vec<_>>()).find(|fields| fields.first() == Some(&device))
+}
+
+#[test]
+fn d_deletes_a_partition() {
+    let (output, table) = fdisk(&[GPT_LAYOUT, &["d", "1", "w"]]);
+    assert!(output.contains("Partition number (1,2, default 2): 1\n\nPartition 1 has been deleted."), "{}", output);
+    assert_eq!(row(&table, "/dev/sdz1"), None, "{}", table);
+    assert_eq!(row(&table, "/dev/sdz2").unwrap()[1..5], ["2099200", "104855551", "102756352", "49G"]);
+
+    let (output, table) = fdisk(&[&["g", "n", "", "", "", "d", "w"]]);
+    assert!(output.contains("Selected partition 1\n\nPartition 1 has been deleted."), "{}", output);
+    assert_eq!(row(&table, "/dev/sdz1"), None, "{}", table);
+}
+
+#[test]
+fn q_leaves_the_disk_untouched() {
+    let (_, written) = fdisk(&[GPT_LAYOUT]);
+    let (_, quit) = fdisk(&[GPT_LAYOUT, &["d", "2", "o", "n", "", "", "", "", "q"]]);
+    assert_eq!(quit, written);
+
+    let (output, table) = fdisk(&[&["g", "n", "", "", "", "q"]]);
+    assert!(output.contains("Created a new partition 1"), "{}", output);
+    assert!(!table.contains("Disklabel type"), "{}", table);
+    assert_eq!(row(&table, "/dev/sdz1"), None, "{}", table);
+}
+
+#[test]
+fn last_sector_takes_sizes_offsets_and_sectors() {
+    let (output, table) = fdisk(&[&["g", "n", "", "", "+512M", "n", "", "", "-1G", "n", "", "", "104808447", "w"]]);
+    assert!(output.contains("Created a new partition 1 of type 'Linux filesystem' and of size 512 MiB."), "{}", output);
+    assert_eq!(row(&table, "/dev/sdz1").unwrap()[1..5], ["2048", "1050623", "1048576", "512M"]);
+    // `-1G` ends a gigabyte before the last usable sector, 104857566
+    assert_eq!(row(&table, "/dev/sdz2").unwrap()[1..5], ["1050624", "102760414", "101709791", "48.5G"]);
+    // An absolute last sector is used as given
+    assert_eq!(row(&table, "/dev/sdz3").unwrap()[1..5], ["102760448", "104808447", "2048000", "1000M"]);
+}
+
+#[test]
+fn sizes_beyond_the_free_space_are_refused() {
+    let (output, table) = fdisk(&[&["g", "n", "", "", "+60G", "+1G", "w"]]);
+    assert!(output.contains("+60G\nValue out of range.\n"), "{}", output);
+    assert_eq!(row(&table, "/dev/sdz1").unwrap()[1..5], ["2048", "2099199", "2097152", "1G"]);
+}
+
+#[test]
+fn a_blank_disk_gets_a_dos_label_with_primary_partitions() {
+    let (output, table) = fdisk(&[&["n", "", "", "", "+1G", "n", "p", "", "", "+1G", "n", "e", "", "", "", "w"]]);
+    assert!(output.contains("Created a new DOS (MBR) disklabel with disk identifier 0x"), "{}", output);
+    assert!(output.contains("   p   primary (0 primary, 0 extended, 4 free)"), "{}", output);
+    assert!(output.contains("   p   primary (1 primary, 0 extended, 3 free)"), "{}", output);
+    assert!(output.contains("Select (default p): e\nExtended partitions are not used by the Arch install; creating a primary partition instead."), "{}", output);
+    assert!(table.contains("Disklabel type: dos"), "{}", table);
+    assert_eq!(row(&table, "/dev/sdz1").unwrap()[1..], ["2048", "2099199", "2097152", "1G", "83", "Linux"]);
+    assert_eq!(row(&table, "/dev/sdz2").unwrap()[1..], ["2099200", "4196351", "2097152", "1G", "83", "Linux"]);
+    assert_eq!(row(&table, "/dev/sdz3").unwrap()[5..], ["83", "Linux"]);
+}
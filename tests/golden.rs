@@ -0,0 +1,84 @@
+//! Plays the inputs in `tests/golden/<gpu>-<device>.input` through the bundled
+//! lesson and compares the transcript with `<gpu>-<device>.transcript`.
+//!
+//! After an intended change to the lesson, regenerate the transcripts with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+use std::fs;
+use std::path::PathBuf;
+use archtry::frontend::Memory;
+use archtry::models::{DeviceType, GpuType, UserChoices};
+use archtry::simulator::run_simulation;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// Answers from an input file; as in `--script` files, `#` lines are comments
+fn read_inputs(text: &str) -> Vec<&str> {
+    text.lines().filter(|line| !line.starts_with('#')).collect()
+}
+
+fn check(gpu_type: GpuType, device_type: DeviceType) {
+    colored::control::set_override(false);
+    let name = format!("{}-{}", gpu_type.id(), device_type.id());
+    let input_path = golden_dir().join(format!("{}.input", name));
+    let transcript_path = golden_dir().join(format!("{}.transcript", name));
+
+    let inputs = fs::read_to_string(&input_path).unwrap_or_else(|err| panic!("{}: {}", input_path.display(), err));
+    let mut frontend = Memory::new(read_inputs(&inputs));
+    let flow = run_simulation(&UserChoices::new(gpu_type, device_type), &mut frontend);
+    assert!(flow.is_continue(), "{}: the run ended early\n{}", name, frontend.output());
+    assert_eq!(frontend.remaining(), 0, "{}: inputs left over\n{}", name, frontend.output());
+
+    let actual = frontend.output();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&transcript_path, actual).unwrap_or_else(|err| panic!("{}: {}", transcript_path.display(), err));
+        return;
+    }
+    let expected = fs::read_to_string(&transcript_path).unwrap_or_else(|err| {
+        panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", transcript_path.display(), err)
+    });
+    if actual != expected {
+        panic!("{}: transcript differs from the golden file\n{}", name, first_difference(&expected, actual));
+    }
+}
+
+/// Points at the first differing line, with a little context
+fn first_difference(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let index = expected.iter().zip(&actual).position(|(e, a)| e != a).unwrap_or(expected.len().min(actual.len()));
+    let context = index.saturating_sub(3);
+    let mut report = format!("first difference at line {}:\n", index + 1);
+    for line in &expected[context..index] {
+        report.push_str(&format!("  {}\n", line));
+    }
+    for line in expected.iter().skip(index).take(3) {
+        report.push_str(&format!("- {}\n", line));
+    }
+    for line in actual.iter().skip(index).take(3) {
+        report.push_str(&format!("+ {}\n", line));
+    }
+    report
+}
+
+macro_rules! golden {
+    ($($test:ident: $gpu:ident, $device:ident;)*) => {
+        $(
+            #[test]
+            fn $test() {
+                check(GpuType::$gpu, DeviceType::$device);
+            }
+        )*
+    };
+}
+
+golden! {
+    amd_laptop: Amd, Laptop;
+    amd_pc: Amd, Pc;
+    intel_laptop: Intel, Laptop;
+    intel_pc: Intel, Pc;
+    nvidia_laptop: Nvidia, Laptop;
+    nvidia_pc: Nvidia, Pc;
+}
@@ -0,0 +1,52 @@
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::PathBuf;
+use archtry::history;
+
+fn saved() -> String {
+    fs::read_to_string(history::path().unwrap()).unwrap()
+}
+
+/// The steps share the history, and a refused file turns saving off for the
+/// rest of the run, so they go in order
+#[test]
+fn history_is_deduplicated_capped_and_private() {
+    let dir = std::env::temp_dir().join(format!("archtry-history-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    // SAFETY: the only test in this binary, so no other thread reads the environment
+    unsafe {
+        std::env::set_var("XDG_STATE_HOME", &dir);
+        std::env::remove_var("ARCHTRY_NO_HISTORY");
+    }
+    assert_eq!(history::path(), Some(dir.join("archtry").join("history")));
+
+    // Repeating a command moves it to the end instead of storing it twice
+    for command in ["lsblk", "lsblk", "ip a", "lsblk"] {
+        history::add(command).unwrap();
+    }
+    assert_eq!(history::entries(), ["ip a", "lsblk"]);
+    assert_eq!(saved(), "ip a\nlsblk\n");
+    let mode = |path: PathBuf| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(dir.join("archtry")), 0o700);
+    assert_eq!(mode(history::path().unwrap()), 0o600);
+
+    // Past the cap, the oldest entries go
+    history::set_max_entries(3);
+    for command in ["fdisk -l", "mount /dev/sdz2 /mnt", "lsblk -f"] {
+        history::add(command).unwrap();
+    }
+    assert_eq!(history::entries(), ["fdisk -l", "mount /dev/sdz2 /mnt", "lsblk -f"]);
+    assert_eq!(saved(), "fdisk -l\nmount /dev/sdz2 /mnt\nlsblk -f\n");
+
+    // A history file planted as a symlink is never written through
+    history::clear().unwrap();
+    let target = dir.join("target");
+    fs::write(&target, "untouched\n").unwrap();
+    symlink(&target, history::path().unwrap()).unwrap();
+    assert!(history::add("passwd").is_err());
+    history::add("reboot").unwrap();
+    assert_eq!(fs::read_to_string(&target).unwrap(), "untouched\n");
+    assert_eq!(history::entries(), ["passwd", "reboot"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
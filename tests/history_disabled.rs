@@ -0,0 +1,20 @@
+use std::fs;
+use archtry::history;
+
+/// `disable` lasts for the rest of the process, so it has a test binary of its own
+#[test]
+fn disabled_history_writes_nothing() {
+    let dir = std::env::temp_dir().join(format!("archtry-history-disabled-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    // SAFETY: the only test in this binary, so no other thread reads the environment
+    unsafe {
+        std::env::set_var("XDG_STATE_HOME", &dir);
+        std::env::remove_var("ARCHTRY_NO_HISTORY");
+    }
+
+    history::disable();
+    history::add("lsblk").unwrap();
+    history::add("ip a").unwrap();
+    assert_eq!(history::entries(), ["lsblk", "ip a"]);
+    assert!(!dir.exists());
+}
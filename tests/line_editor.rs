@@ -0,0 +1,158 @@
+use std::io;
+use archtry::input_handler::edit;
+use unicode_width::UnicodeWidthStr;
+
+const LEFT: &str = "\x1B[D";
+const RIGHT: &str = "\x1B[C";
+const BACKSPACE: &str = "\x7F";
+const DELETE: &str = "\x1B[3~";
+const ALT_B: &str = "\x1Bb";
+const ALT_F: &str = "\x1Bf";
+const ALT_D: &str = "\x1Bd";
+
+/// Presses `keys` at an empty prompt, returning the line and everything drawn
+fn type_keys(keys: &str, history: &[&str]) -> (io::Result<String>, String) {
+    let history: Vec<String> = history.iter().map(|entry| entry.to_string()).collect();
+    let mut drawn = Vec::new();
+    let line = edit(&mut keys.as_bytes(), &mut drawn, "", &history, None);
+    (line, String::from_utf8(drawn).unwrap())
+}
+
+/// The line after pressing `keys` and Enter
+fn edited(keys: &str) -> String {
+    type_keys(&format!("{}\r", keys), &[]).0.unwrap()
+}
+
+/// Column the cursor was left at by the last redraw
+fn cursor_column(drawn: &str) -> usize {
+    let last = &drawn[drawn.rfind("\x1B[K").expect("the line was drawn")..];
+    last.rsplit_once("\r\x1B[").map_or(0, |(_, moved)| moved.trim_end().trim_end_matches('C').parse().unwrap())
+}
+
+#[test]
+fn left_and_right_step_over_combining_marks() {
+    let cafe = "cafe\u{301}";
+    assert_eq!(edited(&format!("{}{}X", cafe, LEFT)), "cafXe\u{301}");
+    assert_eq!(edited(&format!("{}{}{}{}X", cafe, LEFT, LEFT, RIGHT)), "cafXe\u{301}");
+    assert_eq!(edited(&format!("{}{}", cafe, BACKSPACE)), "caf");
+}
+
+#[test]
+fn left_and_right_step_over_joined_emoji() {
+    let coder = "a\u{1F469}\u{200D}\u{1F4BB}b";
+    assert_eq!(edited(&format!("{}{}{}X", coder, LEFT, LEFT)), "aX\u{1F469}\u{200D}\u{1F4BB}b");
+    assert_eq!(edited(&format!("{}{}{}", coder, LEFT, BACKSPACE)), "ab");
+    assert_eq!(edited(&format!("{}{}{}{}", coder, LEFT, LEFT, DELETE)), "ab");
+}
+
+#[test]
+fn wide_text_moves_the_cursor_by_its_width() {
+    let (_, drawn) = type_keys(&format!("日本語{}", LEFT), &[]);
+    assert_eq!(cursor_column(&drawn), "日本".width());
+    assert_eq!(cursor_column(&drawn), 4);
+
+    let (_, drawn) = type_keys(&format!("a\u{1F469}\u{200D}\u{1F4BB}e\u{301}{}", LEFT), &[]);
+    assert_eq!(cursor_column(&drawn), "a\u{1F469}\u{200D}\u{1F4BB}".width());
+}
+
+#[test]
+fn word_motions_stop_at_alphanumeric_runs() {
+    assert_eq!(edited(&format!("mount /dev/sdz2{}X", ALT_B)), "mount /dev/Xsdz2");
+    assert_eq!(edited(&format!("mount /dev/sdz2{}{}X", ALT_B, ALT_B)), "mount /Xdev/sdz2");
+    assert_eq!(edited(&format!("mount /dev/sdz2\x01{}X", ALT_F)), "mountX /dev/sdz2");
+    assert_eq!(edited(&format!("mount /dev/sdz2\x01{}{}X", ALT_F, ALT_F)), "mount /devX/sdz2");
+    assert_eq!(edited(&format!("mount /dev/sdz2\x01{}", ALT_D)), " /dev/sdz2");
+}
+
+#[test]
+fn word_motions_treat_wide_text_as_words() {
+    assert_eq!(edited(&format!("日本 語{}X", ALT_B)), "日本 X語");
+    assert_eq!(edited(&format!("日本 語\x01{}X", ALT_F)), "日本X 語");
+    let (_, drawn) = type_keys(&format!("日本 語{}", ALT_B), &[]);
+    assert_eq!(cursor_column(&drawn), "日本 ".width());
+}
+
+#[test]
+fn ctrl_w_deletes_back_to_whitespace() {
+    assert_eq!(edited("mount -o rw,noatime\x17"), "mount -o ");
+    assert_eq!(edited("mount -o rw,noatime   \x17"), "mount -o ");
+    assert_eq!(edited("echo 'a b'>>/etc/hosts\x17"), "echo 'a ");
+    assert_eq!(edited("   \x17"), "");
+    assert_eq!(edited(&format!("ls /mnt/boot{}{}{}\x17", LEFT, LEFT, LEFT)), "ls oot");
+}
+
+#[test]
+fn killed_text_can_be_yanked_back() {
+    assert_eq!(edited("echo one two\x17\x01\x19"), "twoecho one ");
+    assert_eq!(edited("echo one two\x17\x17\x19"), "echo one ");
+    assert_eq!(edited("mount /dev/sdz2 /mnt\x01\x1Bf\x0B\x19\x19"), "mount /dev/sdz2 /mnt /dev/sdz2 /mnt");
+    assert_eq!(edited(&format!("cat /etc/hostname{}{}\x15\x05 \x19", ALT_B, LEFT)), "/hostname cat /etc");
+    assert_eq!(edited("lsblk\x19"), "lsblk");
+}
+
+#[test]
+fn home_and_end_jump_to_the_ends() {
+    assert_eq!(edited("sdz\x01/dev/\x05 -l"), "/dev/sdz -l");
+    assert_eq!(edited("b\x01a\x05c\x01\x0B"), "");
+    assert_eq!(edited("abc\x05\x0B\x15"), "");
+}
+
+const HISTORY: &[&str] = &[
+    "mount /dev/sdz2 /mnt",
+    "lsblk",
+    "mount --mkdir /dev/sdz1 /mnt/boot",
+    "mount --mkdir /dev/sdz1 /mnt/boot",
+    "ls /mnt",
+];
+
+/// The line after searching the history with `keys` and pressing Enter
+fn searched(keys: &str) -> String {
+    type_keys(&format!("{}\r", keys), HISTORY).0.unwrap()
+}
+
+#[test]
+fn ctrl_r_finds_the_newest_match() {
+    assert_eq!(searched("\x12mount"), "mount --mkdir /dev/sdz1 /mnt/boot");
+    assert_eq!(searched("\x12mnt"), "ls /mnt");
+    assert_eq!(searched("\x12ls"), "ls /mnt");
+    assert_eq!(searched("\x12lsb"), "lsblk");
+}
+
+#[test]
+fn ctrl_r_again_cycles_to_older_matches_skipping_duplicates() {
+    assert_eq!(searched("\x12mount\x12"), "mount /dev/sdz2 /mnt");
+    assert_eq!(searched("\x12mnt\x12"), "mount --mkdir /dev/sdz1 /mnt/boot");
+    assert_eq!(searched("\x12mnt\x12\x12"), "mount /dev/sdz2 /mnt");
+}
+
+#[test]
+fn ctrl_r_stops_at_the_oldest_match() {
+    let (line, drawn) = type_keys("\x12mount\x12\x12\x12\r", HISTORY);
+    assert_eq!(line.unwrap(), "mount /dev/sdz2 /mnt");
+    assert!(drawn.contains("(failed reverse-i-search)`mount': mount /dev/sdz2 /mnt"), "{:?}", drawn);
+}
+
+#[test]
+fn ctrl_r_without_a_match_keeps_the_line() {
+    let (line, drawn) = type_keys("ls\x12qq\r", HISTORY);
+    assert_eq!(line.unwrap(), "ls");
+    assert!(drawn.contains("(failed reverse-i-search)`qq': ls"), "{:?}", drawn);
+    // Like bash, a query that stops matching keeps the last match
+    assert_eq!(searched("\x12blkid"), "lsblk");
+    assert_eq!(searched("ls\x12mount\x07"), "ls");
+}
+
+#[test]
+fn other_keys_leave_the_search_to_edit_the_match() {
+    assert_eq!(searched(&format!("\x12lsblk{} -f", RIGHT)), "lsblk -f");
+    assert_eq!(searched("\x12mount\x12\x05 -o ro"), "mount /dev/sdz2 /mnt -o ro");
+}
+
+#[test]
+fn end_of_input_is_not_an_empty_line() {
+    for keys in ["", "ls -l", "\x04", "ls\x15\x04"] {
+        let (line, _) = type_keys(keys, HISTORY);
+        assert_eq!(line.unwrap_err().kind(), io::ErrorKind::UnexpectedEof, "{:?}", keys);
+    }
+    assert_eq!(edited("lsblk\x01\x04"), "sblk");
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use archtry::models::Mode;
+use archtry::scenario::Scenario;
+use common::{accepts_in, lesson};
+
+fn accepts(scenario: &Scenario, typed: &str) -> bool {
+    accepts_in(Mode::Guided, scenario, typed)
+}
+
+#[test]
+fn short_and_long_flags_are_the_same() {
+    let scenario = lesson(&["mount /dev/sdz2 /mnt"], "mount --mkdir /dev/sdz1 /mnt/boot", "");
+    assert!(accepts(&scenario, "mount --mkdir /dev/sdz1 /mnt/boot"));
+    assert!(accepts(&scenario, "mount -m /dev/sdz1 /mnt/boot"));
+    assert!(!accepts(&scenario, "mount /dev/sdz1 /mnt/boot"));
+}
+
+#[test]
+fn flag_values_may_be_attached_or_separate() {
+    let scenario = lesson(&[], "mkfs.fat -F 32 /dev/sdz1", "");
+    assert!(accepts(&scenario, "mkfs.fat -F32 /dev/sdz1"));
+    assert!(accepts(&scenario, "mkfs.fat --fat-size=32 /dev/sdz1"));
+    assert!(!accepts(&scenario, "mkfs.fat -F 16 /dev/sdz1"));
+}
+
+#[test]
+fn trailing_slashes_do_not_matter() {
+    let scenario = lesson(&[], "mount /dev/sdz2 /mnt", "");
+    assert!(accepts(&scenario, "mount /dev/sdz2 /mnt/"));
+    assert!(!accepts(&scenario, "mount /dev/sdz2 /media"));
+}
+
+#[test]
+fn packages_may_come_in_any_order() {
+    let scenario = lesson(&["mount /dev/sdz2 /mnt"], "pacstrap -K /mnt base linux linux-firmware", "");
+    assert!(accepts(&scenario, "pacstrap -K /mnt linux-firmware base linux"));
+    assert!(accepts(&scenario, "pacstrap -K /mnt linux base linux-firmware vim"));
+    assert!(!accepts(&scenario, "pacstrap -K /mnt base linux"));
+    assert!(!accepts(&scenario, "pacstrap -K base linux linux-firmware /mnt"));
+}
+
+#[test]
+fn exact_rules_reject_reordered_commands() {
+    let loose = lesson(&[], "useradd -m -G wheel bob", "");
+    assert!(accepts(&loose, "useradd -G wheel -m bob"));
+
+    let exact = lesson(&[], "useradd -m -G wheel bob", "exact = true");
+    assert!(accepts(&exact, "  useradd  -m -G wheel   bob "));
+    assert!(!accepts(&exact, "useradd -G wheel -m bob"));
+}
+
+#[test]
+fn alternatives_are_accepted_too() {
+    let scenario = lesson(&[], "mkfs.ext4 /dev/sdz2", "alternatives = [\"mkfs.fat -F 32 /dev/sdz2\"]");
+    assert!(accepts(&scenario, "mkfs.fat -F 32 /dev/sdz2"));
+    assert!(!accepts(&scenario, "mkfs.fat -F 16 /dev/sdz2"));
+}
+
+#[test]
+fn quotes_are_removed_before_matching() {
+    let scenario = lesson(&[], "echo \"archlinux\" > /etc/hostname", "");
+    assert!(accepts(&scenario, "echo archlinux > /etc/hostname"));
+    assert!(accepts(&scenario, "echo 'archlinux' >/etc/hostname"));
+    assert!(accepts(&scenario, "echo \"arch\"'linux' > /etc/hostname"));
+    assert!(!accepts(&scenario, "echo 'arch linux' > /etc/hostname"));
+    assert!(!accepts(&scenario, "echo \"archlinux > /etc/hostname"));
+}
+
+#[test]
+fn variables_are_expanded_before_matching() {
+    let scenario = lesson(&[], "echo \"archlinux\" > /etc/hostname", "");
+    assert!(accepts(&scenario, "export NAME=archlinux; echo $NAME > /etc/hostname"));
+    assert!(accepts(&scenario, "export NAME=arch; echo \"${NAME}linux\" > /etc/hostname"));
+    assert!(!accepts(&scenario, "export NAME=arch; echo '${NAME}linux' > /etc/hostname"));
+}
+
+#[test]
+fn connectors_decide_whether_the_command_runs() {
+    let scenario = lesson(&[], "mkfs.ext4 /dev/sdz2", "");
+    assert!(accepts(&scenario, "lsblk && mkfs.ext4 /dev/sdz2"));
+    assert!(accepts(&scenario, "false || mkfs.ext4 /dev/sdz2"));
+    assert!(accepts(&scenario, "lsblk; mkfs.ext4 /dev/sdz2"));
+    assert!(!accepts(&scenario, "false && mkfs.ext4 /dev/sdz2"));
+    assert!(!accepts(&scenario, "true || mkfs.ext4 /dev/sdz2"));
+    assert!(!accepts(&scenario, "mkfs.ext4 /dev/sdz2 && mount /dev/sdz2 /mnt"));
+    assert!(!accepts(&scenario, "mkfs.ext4 /dev/sdz2 &&"));
+}
+
+#[test]
+fn tee_append_is_the_same_as_appending() {
+    let scenario = lesson(&[], "echo '127.0.1.1 archlinux' >> /etc/hosts", "");
+    assert!(accepts(&scenario, "echo '127.0.1.1 archlinux' | tee -a /etc/hosts"));
+    assert!(accepts(&scenario, "echo '127.0.1.1 archlinux' | tee --append /etc/hosts"));
+    assert!(!accepts(&scenario, "echo '127.0.1.1 archlinux' | tee /etc/hosts"));
+    assert!(!accepts(&scenario, "echo '127.0.1.1 archlinux' > /etc/hosts"));
+}
@@ -0,0 +1,45 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use clap::ValueEnum;
+use archtry::pacing::{Clock, Pacer, Speed};
+use archtry::settings::Settings;
+
+/// Records requested waits instead of sleeping
+struct Recorder(Rc<RefCell<Vec<Duration>>>);
+
+impl Clock for Recorder {
+    fn sleep(&mut self, duration: Duration) {
+        self.0.borrow_mut().push(duration);
+    }
+}
+
+fn waits(speed: Speed, pauses: &[u64]) -> Vec<Duration> {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut pacer = Pacer::with_clock(speed, Box::new(Recorder(log.clone())));
+    for secs in pauses {
+        pacer.pause(*secs);
+    }
+    log.take()
+}
+
+#[test]
+fn presets_scale_normal_pauses() {
+    assert_eq!(waits(Speed::Instant, &[3]), [Duration::ZERO]);
+    assert_eq!(waits(Speed::Fast, &[2]), [Duration::from_millis(500)]);
+    assert_eq!(waits(Speed::Normal, &[1, 3]), [Duration::from_secs(1), Duration::from_secs(3)]);
+    assert_eq!(waits(Speed::Realistic, &[3]), [Duration::from_secs(6)]);
+}
+
+#[test]
+fn speeds_parse_from_their_names() {
+    for speed in Speed::value_variants() {
+        let name = speed.to_possible_value().unwrap().get_name().to_string();
+        assert_eq!(Speed::from_str(&name, true), Ok(*speed));
+        let settings = Settings::parse(&format!("speed = \"{}\"", name)).unwrap();
+        assert_eq!(settings.speed, Some(*speed));
+    }
+    assert!(Speed::from_str("warp", true).is_err());
+    assert!(Settings::parse("speed = \"warp\"").is_err());
+    assert_eq!(Speed::default(), Speed::Normal);
+}
@@ -0,0 +1,73 @@
+use std::collections::{BTreeMap, HashMap};
+use archtry::config::{get_user_choices, Presets};
+use archtry::frontend::Memory;
+use archtry::models::{GpuType, HintLevel};
+use archtry::pacing::Speed;
+use archtry::settings::Settings;
+use archtry::theme::{self, Theme};
+
+const EXAMPLE: &str = r#"
+speed = "fast"
+theme = "light"
+hints = "partial"
+max_attempts = 5
+
+[colors]
+header = "magenta"
+
+[defaults]
+gpu = "nvidia"
+hostname = "workstation"
+
+[history]
+enabled = false
+max_entries = 50
+"#;
+
+#[test]
+fn example_config_parses() {
+    let settings = Settings::parse(EXAMPLE).unwrap();
+    assert_eq!(settings.speed, Some(Speed::Fast));
+    assert_eq!(settings.theme, Theme::Light);
+    assert_eq!(settings.hints, Some(HintLevel::Partial));
+    assert_eq!(settings.max_attempts, Some(5));
+    assert_eq!(settings.colors["header"], "magenta");
+    assert_eq!(settings.defaults["hostname"], "workstation");
+    assert!(!settings.history.enabled);
+    assert_eq!(settings.history.max_entries, 50);
+}
+
+#[test]
+fn empty_config_keeps_defaults() {
+    let settings = Settings::parse("").unwrap();
+    assert_eq!(settings.speed, None);
+    assert_eq!(settings.theme, Theme::Default);
+    assert!(settings.history.enabled);
+    assert_eq!(settings.history.max_entries, archtry::history::DEFAULT_MAX_ENTRIES);
+}
+
+#[test]
+fn mistakes_are_reported() {
+    assert!(Settings::parse("sped = \"fast\"").is_err());
+    assert!(Settings::parse("speed = \"warp\"").is_err());
+    assert!(Settings::parse("[history]\nmax = 5").is_err());
+}
+
+#[test]
+fn every_role_can_be_recoloured() {
+    let roles = ["header", "success", "warning", "hint", "error", "user", "muted"];
+    let colors = roles.iter().map(|role| (role.to_string(), "magenta".to_string())).collect();
+    assert_eq!(theme::apply(Theme::Default, &colors), Ok(()));
+    let unknown = BTreeMap::from([("dimmed".to_string(), "white".to_string())]);
+    assert_eq!(theme::apply(Theme::Default, &unknown), Err("unknown colour role 'dimmed'".to_string()));
+}
+
+#[test]
+fn defaults_are_preselected() {
+    colored::control::set_override(false);
+    let mut frontend = Memory::new(["", ""]);
+    let defaults = HashMap::from([("gpu".to_string(), "NVIDIA".to_string()), ("device".to_string(), "pc".to_string())]);
+    let choices = get_user_choices(&mut frontend, &Presets { defaults, ..Presets::default() });
+    assert!(matches!(choices.gpu_type, GpuType::Nvidia), "{}", frontend.output());
+    assert!(frontend.output().contains("Do you want a laptop or PC? [PC]"), "{}", frontend.output());
+}
@@ -0,0 +1,473 @@
+use std::io;
+use std::ops::ControlFlow;
+use std::time::Duration;
+use archtry::config::{get_user_choices, Presets};
+use archtry::frontend::{Frontend, Memory};
+use archtry::input_handler::Completer;
+use archtry::models::{DeviceType, GpuType, UserChoices};
+use archtry::models::Mode;
+use archtry::scenario::Scenario;
+use archtry::session::Checkpoint;
+use archtry::simulator::{choose_chapter, resume_scenario, run_scenario, run_simulation, Options};
+
+/// Answers for a complete AMD laptop install, including a few detours a
+/// learner might take
+const FULL_RUN: &[&str] = &[
+    "", // GPU: AMD
+    "", // device: Laptop
+    "ip a",
+    "iwctl station wlan0 get-networks",
+    "iwctl station wlan0 connect archtry_wifi",
+    "lsblk",
+    "fdisk /dev/sdz",
+    "g",
+    "n", "", "", "+1G",
+    "t", "uefi",
+    "n", "", "", "",
+    "p",
+    "w",
+    "fdisk -l /dev/sdz",
+    "mount /dev/sdz2 /mnt", // out of order
+    "mkfs.fat -F 32 /dev/sdz1",
+    "mkfs.ext4 /dev/sdz2",
+    "mount /dev/sdz2 /mnt",
+    "mount --mkdir /dev/sdz1 /mnt/boot/efi",
+    "pacstrap -K /mnt base base-devel linux linux-firmware amd-ucode grub efibootmgr",
+    "genfstab -U /mnt >> /mnt/etc/fstab",
+    "arch-chroot /mnt",
+    "", // hostname
+    "echo \"archlinux\" > /etc/hostname",
+    "echo '127.0.1.1 archlinux' >> /etc/hosts",
+    "", // region
+    "", // city
+    "ln -sf /usr/share/zoneinfo/Asia/Riyadh /etc/localtime",
+    "hwclock --systohc",
+    "sed -i 's/^#en_US.UTF-8 UTF-8/en_US.UTF-8 UTF-8/' /etc/locale.gen",
+    "locale-gen",
+    "echo \"LANG=en_US.UTF-8\" > /etc/locale.conf",
+    "pw", "pw", // root password
+    "bob",
+    "useradd -mG wheel bob",
+    "pw", "pw",
+    "grub-install --target=x86_64-efi --efi-directory=/boot/efi --bootloader-id=GRUB",
+    "grub-mkconfig -o /boot/grub/grub.cfg",
+    "", // desktop
+    "pacman -S gnome",
+    "exit",
+    "umount -R /mnt",
+    "reboot",
+];
+
+fn run(inputs: &[&str]) -> (ControlFlow<()>, Memory) {
+    colored::control::set_override(false);
+    let mut frontend = Memory::new(inputs.iter().copied());
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let flow = run_simulation(&choices, &mut frontend);
+    (flow, frontend)
+}
+
+#[test]
+fn full_run_completes() {
+    let (flow, frontend) = run(FULL_RUN);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert_eq!(frontend.remaining(), 0, "{}", output);
+    assert!(output.contains("Created a new GPT disklabel"), "{}", output);
+    assert!(output.contains("Password updated."), "{}", output);
+    assert!(output.contains("System rebooting..."), "{}", output);
+    assert!(output.contains("You've learned the basics"), "{}", output);
+}
+
+#[test]
+fn out_of_order_commands_are_retried() {
+    let (_, frontend) = run(FULL_RUN);
+    let output = frontend.output();
+    let rejected = output.find("# mount /dev/sdz2 /mnt\nmount: /mnt: wrong fs type, bad option, bad superblock on /dev/sdz2");
+    assert!(rejected.is_some(), "{}", output);
+}
+
+#[test]
+fn quit_ends_the_run_early() {
+    let (flow, frontend) = run(&["", "", ":quit", "ip a"]);
+    assert!(flow.is_break());
+    assert_eq!(frontend.remaining(), 1);
+    assert!(!frontend.output().contains("System rebooting..."));
+}
+
+#[test]
+fn exit_is_only_a_command_where_the_lesson_expects_it() {
+    let (_, frontend) = run(&["", "", "exit", "ip a"]);
+    let output = frontend.output();
+    assert!(output.contains("# exit\nThat would run, but it is not this step: Show network interfaces\nTo leave the lesson, type :quit."), "{}", output);
+    assert!(output.contains("1: wlan0"), "{}", output);
+}
+
+#[test]
+fn lesson_commands_do_not_count_as_attempts() {
+    let (_, frontend) = run(&["", "", ":help", ":frobnicate", ":back", ":save", "ip a", ":quit"]);
+    let output = frontend.output();
+    assert!(output.contains(":answer  reveal the whole command"), "{}", output);
+    assert!(output.contains("Unknown lesson command ':frobnicate'. Type :help for the list."), "{}", output);
+    assert!(output.contains("There is no earlier step to go back to."), "{}", output);
+    assert!(output.contains("Saving is off in this run."), "{}", output);
+    assert!(!output.contains("command not found"), "{}", output);
+}
+
+#[test]
+fn skipped_steps_are_done_for_the_learner() {
+    let mut inputs = FULL_RUN.to_vec();
+    let fdisk = inputs.iter().position(|input| *input == "fdisk /dev/sdz").unwrap();
+    let listed = inputs.iter().position(|input| *input == "fdisk -l /dev/sdz").unwrap();
+    inputs.splice(fdisk..listed, [":skip"]);
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("Skipped; the step was done with: fdisk /dev/sdz"), "{}", output);
+    assert!(output.contains("Steps: 28 completed, 1 skipped"), "{}", output);
+}
+
+#[test]
+fn back_redoes_the_previous_step() {
+    let mut inputs = FULL_RUN.to_vec();
+    let format = inputs.iter().position(|input| *input == "mkfs.ext4 /dev/sdz2").unwrap() + 1;
+    inputs.splice(format..format, [":back", "mkfs.ext4 /dev/sdz2"]);
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert_eq!(output.matches("# Format the root partition as ext4").count(), 2, "{}", output);
+    assert!(output.contains("Steps: 29 completed"), "{}", output);
+}
+
+#[test]
+fn menu_starts_over_at_a_chapter() {
+    let start = FULL_RUN.iter().position(|input| input.starts_with("grub-install")).unwrap();
+    let mut inputs = vec!["", "", ":menu", "1", ":menu", "Bootloader"];
+    inputs.extend(&FULL_RUN[start..]);
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert_eq!(frontend.remaining(), 0, "{}", output);
+    assert!(output.contains("Starting at chapter 'Bootloader'"), "{}", output);
+    assert!(output.contains("Steps: 6 completed"), "{}", output);
+}
+
+#[test]
+fn end_of_input_exits_like_ctrl_d() {
+    let (flow, frontend) = run(&["", "", "ip a"]);
+    assert!(flow.is_break());
+    assert!(frontend.output().ends_with("exit\n"), "{}", frontend.output());
+}
+
+#[test]
+fn unknown_commands_are_rejected() {
+    let (_, frontend) = run(&["", "", "ip link", "mout /dev/sdz2 /mnt"]);
+    let output = frontend.output();
+    assert!(output.contains("# ip link\nThat would run, but it is not this step: Show network interfaces"), "{}", output);
+    assert!(output.contains("# mout /dev/sdz2 /mnt\nbash: mout: command not found"), "{}", output);
+}
+
+#[test]
+fn wrong_commands_get_the_real_tools_errors() {
+    let mut inputs = FULL_RUN.to_vec();
+    let fdisk = inputs.iter().position(|input| *input == "fdisk /dev/sdz").unwrap();
+    inputs.splice(fdisk..fdisk, ["mount /dev/sdz3 /mnt", "fdisk --wipe /dev/sdz"]);
+    let pacstrap = inputs.iter().position(|input| input.starts_with("pacstrap")).unwrap();
+    inputs.insert(pacstrap, "pacstrap -x /mnt base");
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("mount: /mnt: special device /dev/sdz3 does not exist."), "{}", output);
+    assert!(output.contains("fdisk: unrecognized option '--wipe'"), "{}", output);
+    assert!(output.contains("pacstrap: invalid option -- 'x'"), "{}", output);
+}
+
+#[test]
+fn the_shell_keeps_a_working_directory() {
+    let mut inputs = FULL_RUN.to_vec();
+    let efi = inputs.iter().position(|input| input.ends_with("/mnt/boot/efi")).unwrap();
+    inputs.splice(efi..=efi, ["mkdir /mnt/boot/efi", "mkdir -p /mnt/boot/efi", "cd /mnt/boot", "pwd", "ls", "cd /mnt/nowhere", "cd", "mount /dev/sdz1 /mnt/boot/efi"]);
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("# mkdir /mnt/boot/efi\nmkdir: cannot create directory '/mnt/boot/efi': No such file or directory"), "{}", output);
+    assert!(output.contains("root@archiso /mnt/boot # pwd\n/mnt/boot\n"), "{}", output);
+    assert!(output.contains("root@archiso /mnt/boot # ls\nefi\n"), "{}", output);
+    assert!(output.contains("# cd /mnt/nowhere\nbash: cd: /mnt/nowhere: No such file or directory"), "{}", output);
+    assert!(output.contains("root@archiso ~ # mount /dev/sdz1 /mnt/boot/efi\nEFI partition mounted."), "{}", output);
+    assert!(!output.contains("command not found"), "{}", output);
+    assert!(output.contains("Steps: 29 completed"), "{}", output);
+    assert!(output.contains("Wrong attempts: 1"), "{}", output);
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    let (_, frontend) = run(&["", "", "ip a |", "exit"]);
+    assert!(frontend.output().contains("bash: syntax error: unexpected end of file"));
+}
+
+#[test]
+fn choices_accept_names_and_numbers() {
+    let mut frontend = Memory::new(["nvidia", "2"]);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    assert!(matches!(choices.gpu_type, GpuType::Nvidia));
+    assert!(matches!(choices.device_type, DeviceType::Pc));
+}
+
+#[test]
+fn invalid_choices_are_asked_again() {
+    let mut frontend = Memory::new(["amd gpu", "4", "intel", ""]);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    assert!(matches!(choices.gpu_type, GpuType::Intel));
+    assert!(matches!(choices.device_type, DeviceType::Laptop));
+    assert_eq!(frontend.output().matches("Choose one of: AMD, Intel, NVIDIA").count(), 2);
+}
+
+#[test]
+fn report_card_lists_struggled_steps() {
+    let (flow, frontend) = run(FULL_RUN);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    let report = &output[output.find("Report card").expect("no report card")..];
+    assert!(report.contains("Wrong attempts: 1"), "{}", report);
+    assert!(report.contains("Format the EFI partition as FAT32 (2 attempts, 0s)"), "{}", report);
+    assert!(report.contains("https://wiki.archlinux.org/title/Installation_guide#Format_the_partitions"), "{}", report);
+}
+
+fn run_in(mode: Mode, inputs: &[&str]) -> (ControlFlow<()>, Memory) {
+    colored::control::set_override(false);
+    let mut frontend = Memory::new(inputs.iter().copied());
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let options = Options { mode, ..Options::default() };
+    let flow = run_scenario(&Scenario::bundled(), &choices, &options, &mut frontend);
+    (flow, frontend)
+}
+
+#[test]
+fn practice_mode_reveals_hints_progressively() {
+    let (_, frontend) = run_in(Mode::Practice, &["", "", ":hint", "ipp a", "ip-a", ":hint", ":hint", ":hint", "ip a", "exit"]);
+    let output = frontend.output();
+    let first_step = &output[..output.find("1: wlan0").unwrap_or(output.len())];
+    let reveals: Vec<&str> = first_step.lines().filter(|line| line.starts_with("[hint]")).collect();
+    assert_eq!(reveals, ["[hint] use: ip", "[hint] use: ip", "[hint] use: ip", "[hint] flags: ip …", "[hint] type: ip a", "[hint] type: ip a"], "{}", output);
+    assert!(first_step.contains("The hint already shows the whole command."), "{}", output);
+}
+
+#[test]
+fn exam_mode_hides_hints_and_details() {
+    let mut inputs = FULL_RUN.to_vec();
+    inputs.insert(2, ":hint");
+    let (flow, frontend) = run_in(Mode::Exam, &inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(!output.contains("[hint]"), "{}", output);
+    assert!(output.contains("Hints are not available in exam mode."), "{}", output);
+    assert!(output.contains("# Show network interfaces [00:"), "{}", output);
+    assert!(output.contains("Score: 99/100 (A)"), "{}", output);
+    assert!(!output.contains("Steps to review:"), "{}", output);
+}
+
+#[test]
+fn quit_saves_a_session_that_resumes() {
+    colored::control::set_override(false);
+    let path = std::env::temp_dir().join(format!("archtry-session-{}.toml", std::process::id()));
+    let options = Options { checkpoint: Some(path.clone()), ..Options::default() };
+    let split = FULL_RUN.iter().position(|input| *input == "mkfs.ext4 /dev/sdz2").unwrap() + 1;
+    let mut first = FULL_RUN[..split].to_vec();
+    first.push(":quit");
+
+    let mut frontend = Memory::new(first);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let flow = run_scenario(&Scenario::bundled(), &choices, &options, &mut frontend);
+    assert!(flow.is_break(), "{}", frontend.output());
+    assert!(frontend.output().contains("Your progress is saved."), "{}", frontend.output());
+
+    let checkpoint = Checkpoint::load(&path).unwrap().expect("no checkpoint saved");
+    assert_eq!(checkpoint.stage, "format");
+    assert_eq!(checkpoint.vars["gpu"], "amd");
+    assert_eq!(checkpoint.report.steps.len(), 8);
+
+    let mut frontend = Memory::new(FULL_RUN[split..].iter().copied());
+    let choices = UserChoices::new(checkpoint.gpu, checkpoint.device);
+    let flow = resume_scenario(&Scenario::bundled(), checkpoint, &choices, &options, &mut frontend);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert_eq!(frontend.remaining(), 0, "{}", output);
+    assert!(output.contains("Steps: 29 completed"), "{}", output);
+    assert!(!path.exists(), "the finished session was not removed");
+}
+
+#[test]
+fn chapter_starts_with_earlier_stages_done() {
+    colored::control::set_override(false);
+    let start = FULL_RUN.iter().position(|input| input.starts_with("grub-install")).unwrap();
+    let mut inputs = vec!["", "", "cat /etc/hostname"];
+    inputs.extend(&FULL_RUN[start..]);
+    let mut frontend = Memory::new(inputs);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let options = Options { chapter: Some("bootloader".to_string()), ..Options::default() };
+    let flow = run_scenario(&Scenario::bundled(), &choices, &options, &mut frontend);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert_eq!(frontend.remaining(), 0, "{}", output);
+    assert!(output.contains("Starting at chapter 'Bootloader'"), "{}", output);
+    assert!(!output.contains("Setting up Wi-Fi"), "{}", output);
+    assert!(output.contains("root:/ # cat /etc/hostname\narchlinux\n"), "{}", output);
+    assert!(output.contains("Steps: 6 completed"), "{}", output);
+}
+
+#[test]
+fn running_out_of_attempts_offers_an_explanation() {
+    let (_, frontend) = run(&["", "", "ipa", "ip", "ip b", "Explain what went wrong", "ip a", ":quit"]);
+    let output = frontend.output();
+    assert!(output.contains("3 wrong attempts. What now?"), "{}", output);
+    assert!(output.contains("`ip` is close: it also needs `a`; `b` does not belong here."), "{}", output);
+    assert!(output.contains("1: wlan0"), "{}", output);
+}
+
+#[test]
+fn running_out_of_attempts_offers_a_skip() {
+    let mut inputs = FULL_RUN.to_vec();
+    inputs.splice(2..3, ["ipa", "ipa", "ipa", "Skip this step"]);
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("Show network interfaces (skipped after 3 attempts"), "{}", output);
+}
+
+#[test]
+fn retries_do_not_grow_the_stack() {
+    colored::control::set_override(false);
+    let mut inputs = vec!["", ""];
+    inputs.extend(std::iter::repeat_n("ipa", 20_000));
+    inputs.push(":quit");
+    let mut frontend = Memory::new(inputs);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let options = Options { max_attempts: 0, ..Options::default() };
+    let flow = run_scenario(&Scenario::bundled(), &choices, &options, &mut frontend);
+    assert!(flow.is_break());
+    assert_eq!(frontend.remaining(), 0);
+    assert!(!frontend.output().contains("What now?"));
+}
+
+/// A lesson whose `city` select is keyed by `region`
+fn keyed_lesson(regions: &str, cities: &str) -> String {
+    format!(
+        "name = \"keyed\"\n\n[[stages]]\nid = \"time\"\n\n[[stages.steps]]\nkind = \"select\"\nvar = \"region\"\nprompt = \"Region\"\noptions = {}\n\n[[stages.steps]]\nkind = \"select\"\nvar = \"city\"\nprompt = \"City\"\nkeyed_by = \"region\"\n\n[stages.steps.keyed_options]\n{}\n",
+        regions, cities
+    )
+}
+
+#[test]
+fn keyed_selects_need_options_for_every_value() {
+    let complete = keyed_lesson("[\"Asia\", \"Europe\"]", "Asia = [\"Tokyo\"]\nEurope = [\"Paris\"]");
+    assert!(Scenario::parse(&complete).is_ok());
+
+    let missing = keyed_lesson("[\"Asia\", \"Antarctica\"]", "Asia = [\"Tokyo\"]");
+    let err = Scenario::parse(&missing).unwrap_err().to_string();
+    assert_eq!(err, "invalid scenario: select 'city' has no options for region 'Antarctica'");
+
+    let unkeyed = complete.replace("keyed_by = \"region\"", "keyed_by = \"hostname\"");
+    let err = Scenario::parse(&unkeyed).unwrap_err().to_string();
+    assert!(err.contains("keyed by 'hostname', which no earlier select"), "{}", err);
+}
+
+#[test]
+fn password_steps_are_recorded_and_can_be_skipped() {
+    let mut inputs = FULL_RUN.to_vec();
+    let root_password = inputs.iter().position(|input| *input == "pw").unwrap();
+    inputs.splice(root_password..root_password + 2, ["pw", "wp", "", "", "pw", "typo", "Skip this step"]);
+    let (flow, frontend) = run_in(Mode::Exam, &inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("3 wrong attempts. What now? [Skip this step]"), "{}", output);
+    assert!(!output.contains("Explain what went wrong"), "{}", output);
+    assert!(output.contains("Skipped; root has no password yet."), "{}", output);
+    assert!(output.contains("Score: 96/100 (A)"), "{}", output);
+}
+
+#[test]
+fn end_of_input_at_a_password_prompt_quits() {
+    let end = FULL_RUN.iter().position(|input| *input == "pw").unwrap() + 1;
+    let (flow, frontend) = run(&FULL_RUN[..end]);
+    assert!(flow.is_break(), "{}", frontend.output());
+    assert!(frontend.output().ends_with("Retype password: \n"), "{}", frontend.output());
+}
+
+/// A terminal whose shell prompt can no longer be read
+struct BrokenInput(Memory);
+
+impl Frontend for BrokenInput {
+    fn print(&mut self, text: &str) {
+        self.0.print(text);
+    }
+    fn clear_screen(&mut self) {}
+    fn pause(&mut self, secs: u64) {
+        self.0.pause(secs);
+    }
+    fn progress(&mut self, ticks: u64, message: &str, done: &str) {
+        self.0.progress(ticks, message, done);
+    }
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> usize {
+        self.0.select(prompt, items, default)
+    }
+    fn input(&mut self, prompt: &str, default: Option<String>) -> String {
+        self.0.input(prompt, default)
+    }
+    fn password(&mut self, prompt: &str) -> io::Result<String> {
+        self.0.password(prompt)
+    }
+    fn read_command(&mut self, _prompt: &str, _completer: &dyn Completer) -> io::Result<String> {
+        Err(io::Error::other("Input/output error"))
+    }
+    fn read_line(&mut self, prompt: &str) -> io::Result<String> {
+        self.0.read_line(prompt)
+    }
+}
+
+#[test]
+fn unreadable_input_ends_the_run() {
+    colored::control::set_override(false);
+    let mut frontend = BrokenInput(Memory::new(["", ""]));
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    let flow = run_simulation(&choices, &mut frontend);
+    let output = frontend.0.output();
+    assert!(flow.is_break(), "{}", output);
+    assert!(output.contains("Warning: cannot read input: Input/output error"), "{}", output);
+}
+
+#[test]
+fn lesson_commands_are_not_stored_as_answers() {
+    let mut inputs = FULL_RUN.to_vec();
+    let hostname = inputs.iter().position(|input| *input == "arch-chroot /mnt").unwrap() + 1;
+    inputs.insert(hostname, ":quit");
+    let (flow, frontend) = run(&inputs);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("Lesson commands such as :quit only work at the shell prompt"), "{}", output);
+    assert!(output.contains("root:/ # echo \"archlinux\" > /etc/hostname"), "{}", output);
+}
+
+const NO_CHAPTERS: &str = "name = \"flat\"\n\n[[stages]]\nid = \"look\"\n\n[[stages.steps]]\nkind = \"command\"\ncommand = \"lsblk\"\ndescription = \"List disks\"\n";
+
+#[test]
+fn lessons_without_chapters_have_nothing_to_choose() {
+    colored::control::set_override(false);
+    let scenario = Scenario::parse(NO_CHAPTERS).unwrap();
+    let mut frontend = Memory::new(["", "", ":menu", "lsblk"]);
+    let choices = get_user_choices(&mut frontend, &Presets::default());
+    assert_eq!(choose_chapter(&scenario, &choices, &mut frontend), None);
+
+    let flow = run_scenario(&scenario, &choices, &Options::default(), &mut frontend);
+    let output = frontend.output();
+    assert!(flow.is_continue(), "{}", output);
+    assert!(output.contains("This lesson has no chapters."), "{}", output);
+}
+
+#[test]
+#[should_panic(expected = "select \"Pick one\" has no items to choose from")]
+fn selecting_from_nothing_fails_clearly() {
+    Memory::new([""]).select("Pick one", &[], 0);
+}